@@ -0,0 +1,103 @@
+use crate::conversation::{Attachment, Conversation};
+use anyhow::Result;
+use std::collections::HashMap;
+use std::path::Path;
+
+// Writes the downloaded attachments of `docs` into "<stem>_files" next to `path` and
+// points them there. Branch files share the folder; one file per attachment.
+pub fn write(docs: &[Conversation], path: &Path) -> Result<Vec<Conversation>> {
+    write_with(docs, path, HashMap::new())
+}
+
+// `write` for turns added to an export of `old`: attachments it has already keep their
+// files, new ones don't take their names.
+pub fn append(docs: &[Conversation], path: &Path, old: &Conversation) -> Result<Vec<Conversation>> {
+    let mut written = HashMap::new();
+    old.clone().attachments_mut(&mut |a| {
+        if let Some(rel) = &a.path {
+            written.insert(key(a), rel.clone());
+        }
+    });
+    write_with(docs, path, written)
+}
+
+fn key(a: &Attachment) -> String {
+    a.pointer.clone().unwrap_or_else(|| a.name.clone())
+}
+
+// `written` maps attachments already in the folder to their paths.
+fn write_with(docs: &[Conversation], path: &Path, mut written: HashMap<String, String>) -> Result<Vec<Conversation>> {
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let folder = format!("{}_files", stem);
+    let dir = path.with_file_name(&folder);
+
+    let mut out = Vec::new();
+    for doc in docs {
+        let mut doc = doc.clone();
+        let mut result = Ok(());
+        doc.attachments_mut(&mut |a| {
+            let Some(data) = a.data.clone().filter(|_| result.is_ok()) else { return };
+            let key = key(a);
+            if let Some(rel) = written.get(&key) {
+                a.path = Some(rel.clone());
+                return;
+            }
+            let name = unique(&file_name(&a.name, &data), written.values());
+            let rel = format!("{}/{}", folder, name);
+            result = std::fs::create_dir_all(&dir).and_then(|_| std::fs::write(dir.join(&name), data.as_slice()));
+            if result.is_ok() {
+                a.path = Some(rel.clone());
+                written.insert(key, rel);
+            }
+        });
+        result?;
+        out.push(doc);
+    }
+    Ok(out)
+}
+
+// The attachment's own name made safe, with an extension guessed from the content when
+// it has none (generated images are only known by their file id).
+fn file_name(name: &str, data: &[u8]) -> String {
+    let mut name: String = name
+        .chars()
+        .map(|c| if c.is_control() || "/\\:*?\"<>|".contains(c) { '_' } else { c })
+        .collect();
+    name = name.trim().trim_matches('.').to_string();
+    if name.is_empty() {
+        name = "file".into();
+    }
+    if !name.contains('.')
+        && let Some(ext) = sniff(data)
+    {
+        name = format!("{}.{}", name, ext);
+    }
+    name
+}
+
+fn sniff(data: &[u8]) -> Option<&'static str> {
+    match data {
+        [0x89, b'P', b'N', b'G', ..] => Some("png"),
+        [0xFF, 0xD8, 0xFF, ..] => Some("jpg"),
+        [b'G', b'I', b'F', b'8', ..] => Some("gif"),
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Some("webp"),
+        [b'%', b'P', b'D', b'F', ..] => Some("pdf"),
+        _ => None,
+    }
+}
+
+// Two uploads called "image.png" must not overwrite each other.
+fn unique<'a>(name: &str, taken: impl Iterator<Item = &'a String> + Clone) -> String {
+    let is_taken = |candidate: &str| taken.clone().any(|rel| rel.rsplit('/').next() == Some(candidate));
+    if !is_taken(name) {
+        return name.to_string();
+    }
+    let (stem, ext) = match name.rsplit_once('.') {
+        Some((stem, ext)) => (stem, format!(".{}", ext)),
+        None => (name, String::new()),
+    };
+    (2..)
+        .map(|n| format!("{}-{}{}", stem, n, ext))
+        .find(|candidate| !is_taken(candidate))
+        .unwrap()
+}
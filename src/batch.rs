@@ -0,0 +1,73 @@
+use crate::conversation::Conversation;
+use crate::i18n::Lang;
+use crate::progress::Progress;
+use crate::relay::Relay;
+use crate::{fetch_and_convert, import, save, source, Export, Format};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+pub const DEFAULT_JOBS: usize = 4;
+pub const DEFAULT_NAMING: &str = "{title}-{id}";
+
+// What every job in a batch shares.
+#[derive(Clone, Debug)]
+pub struct JobOptions {
+    pub dir: PathBuf,
+    pub export: Export,
+    pub relays: Vec<Relay>,
+    // of the error messages
+    pub lang: Lang,
+}
+
+#[derive(Clone, Debug)]
+pub struct JobResult {
+    pub url: String,
+    pub result: Result<Vec<PathBuf>, String>,
+}
+
+// One URL per line; blank lines and `#` comments are skipped.
+pub fn parse_urls(text: &str) -> Vec<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+// Fetches one share link and saves it into `dir`. Runs once a permit is free, so
+// spawning every job up front still keeps at most `permits` requests in flight.
+pub async fn job(url: String, opts: JobOptions, permits: Arc<Semaphore>) -> JobResult {
+    let _permit = permits.acquire_owned().await;
+    let result = async {
+        let assets = opts.export.format == Format::Markdown;
+        let conv = fetch_and_convert(url.clone(), opts.relays, assets, Progress::none()).await?;
+        let path = opts.dir.join(file_name(&conv, &opts.export.format, &opts.export.naming));
+        save(&opts.export, &opts.export.docs(conv), &path)
+    }
+    .await
+    .map_err(|e| source::error_text(&e, opts.lang));
+    JobResult { url, result }
+}
+
+// `naming` with {title}, {id} (the first 8 characters of the share id) and {date}
+// filled in, plus the extension; safe on every platform we ship for.
+pub fn file_name(conv: &Conversation, format: &Format, naming: &str) -> String {
+    let title: String = conv.title.chars().take(80).collect();
+    let id: String = conv.share_id.as_deref().unwrap_or_default().chars().take(8).collect();
+    let name = naming.replace("{title}", title.trim()).replace("{id}", &id).replace("{date}", &import::date(conv));
+    let stem: String = name
+        .chars()
+        .map(|c| if c.is_control() || "/\\:*?\"<>|".contains(c) { '_' } else { c })
+        .collect();
+    // "{title}-{id}" without an id must not leave "title-"
+    let mut stem = stem.trim_matches(|c: char| c.is_whitespace() || "-_.".contains(c)).to_string();
+    if stem.is_empty() {
+        stem = "chatgpt_conversation".into();
+    }
+    format!("{}.{}", stem, format.extension())
+}
+
+pub fn read_url_file(path: &Path) -> anyhow::Result<Vec<String>> {
+    Ok(parse_urls(&std::fs::read_to_string(path)?))
+}
@@ -0,0 +1,389 @@
+use crate::batch::{self, JobOptions, DEFAULT_JOBS};
+use crate::conversation::Category;
+use crate::i18n::Lang;
+use crate::import;
+use crate::library;
+use crate::sync;
+use crate::progress::Progress;
+use crate::relay::{self, Relay};
+use crate::settings::Settings;
+use crate::template::{self, Template};
+use crate::watch;
+use crate::{fetch_and_convert, render, save, Branches, Citations, Export, Format, Math};
+use anyhow::Result;
+use clap::{Args, Parser, Subcommand};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+#[derive(Parser)]
+#[command(name = "kraken", version, about = "Save public ChatGPT share threads as Markdown or PDF")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Fetch a share link and convert it
+    Fetch {
+        /// Public share URL, e.g. https://chatgpt.com/share/<id>
+        url: String,
+        /// Output file; written to stdout when omitted
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        #[command(flatten)]
+        opts: ExportArgs,
+    },
+    /// Fetch many share links into a folder
+    Batch {
+        /// Share URLs (combined with --input)
+        urls: Vec<String>,
+        /// File with one URL per line
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+        /// Folder to save conversations into
+        #[arg(short, long, default_value = ".")]
+        dir: PathBuf,
+        /// How many links to fetch at the same time
+        #[arg(short, long, default_value_t = DEFAULT_JOBS)]
+        jobs: usize,
+        #[command(flatten)]
+        opts: ExportArgs,
+    },
+    /// Convert conversations from the account data export (zip or conversations.json), offline
+    Import {
+        /// The export zip or the conversations.json from it
+        path: PathBuf,
+        /// Only list the conversations: number, date, id and title
+        #[arg(short, long)]
+        list: bool,
+        /// Conversations to convert, by number from --list or by id (repeat or separate
+        /// with commas) [default: all]
+        #[arg(short, long, value_delimiter = ',')]
+        pick: Vec<String>,
+        /// Folder to save conversations into
+        #[arg(short, long, default_value = ".")]
+        dir: PathBuf,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Print the built-in Markdown template, to start your own from
+    Template,
+    /// List the files saved so far, newest first: date, format, title, path, source and
+    /// whether it is watched
+    Library {
+        /// Mark files to be checked by `kraken watch` (and the window)
+        #[arg(long)]
+        watch: Vec<PathBuf>,
+        /// Stop watching files
+        #[arg(long)]
+        unwatch: Vec<PathBuf>,
+    },
+    /// Fetch conversations saved earlier again and append their new turns to the files
+    /// (the files keep their format; Markdown edits above the new turns are kept)
+    Update {
+        /// Files from `kraken library`
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        #[command(flatten)]
+        opts: ExportArgs,
+    },
+    /// Keep checking files from the library for new turns and append them as they come;
+    /// runs until stopped
+    Watch {
+        /// Files to check [default: the ones marked as watched]
+        files: Vec<PathBuf>,
+        /// Minutes between checks of a file [default: the one set in the window]
+        #[arg(short = 'n', long)]
+        interval: Option<u64>,
+        #[command(flatten)]
+        opts: ExportArgs,
+    },
+}
+
+#[derive(Args)]
+struct OutputArgs {
+    /// Output format: md, pdf, html or json
+    #[arg(short, long, default_value = "md")]
+    format: Format,
+    /// Edited/regenerated branches: current, inline or files
+    #[arg(short, long, default_value = "current")]
+    branches: Branches,
+    /// Sections to leave out: code, browsing, tools, system (separate with commas)
+    #[arg(long, value_delimiter = ',')]
+    hide: Vec<Category>,
+    /// Start Markdown files with YAML front matter (title, source, times, models, ...)
+    #[arg(long)]
+    front_matter: bool,
+    /// Language of the labels in the output: en or ru [default: the one set in the window]
+    #[arg(long)]
+    lang: Option<Lang>,
+    /// How Markdown marks citations of web sources: footnotes or links
+    #[arg(long, default_value = "footnotes")]
+    citations: Citations,
+    /// Markdown math: keep the \( \) and \[ \] delimiters or rewrite them to $ and $$
+    #[arg(long, default_value = "keep")]
+    math: Math,
+    /// File names in batch and import: {title}, {id} (share id) and {date} [default: the
+    /// one set in the window]
+    #[arg(long)]
+    name: Option<String>,
+    /// Markdown template file (see `kraken template`) [default: built-in]
+    #[arg(short, long)]
+    template: Option<PathBuf>,
+}
+
+impl OutputArgs {
+    fn export(&self) -> Result<Export> {
+        let template = match &self.template {
+            Some(path) => Template::load(path)?,
+            None => Template::default(),
+        };
+        Ok(Export {
+            format: self.format.clone(),
+            branches: self.branches.clone(),
+            hidden: self.hide.clone(),
+            front_matter: self.front_matter,
+            lang: self.lang.unwrap_or_else(|| Settings::load().output_language),
+            template,
+            citations: self.citations,
+            math: self.math,
+            naming: self.name.clone().unwrap_or_else(|| Settings::load().naming),
+        })
+    }
+}
+
+#[derive(Args)]
+struct ExportArgs {
+    #[command(flatten)]
+    output: OutputArgs,
+    /// Relays to try in order: direct, jina or a URL template with {url}/{url_encoded}
+    /// (repeat or separate with commas) [default: jina]
+    #[arg(short, long = "relay", value_delimiter = ',')]
+    relays: Vec<Relay>,
+}
+
+impl ExportArgs {
+    fn relays(&self) -> Vec<Relay> {
+        if self.relays.is_empty() {
+            relay::default_chain()
+        } else {
+            self.relays.clone()
+        }
+    }
+}
+
+pub async fn run() -> ExitCode {
+    let cli = Cli::parse();
+    let res = match cli.command {
+        Command::Fetch { url, output, opts } => fetch(url, output, opts).await,
+        Command::Batch { urls, input, dir, jobs, opts } => run_batch(urls, input, dir, jobs, opts).await,
+        Command::Import { path, list, pick, dir, output } => {
+            output.export().and_then(|export| run_import(path, list, pick, dir, export))
+        }
+        Command::Template => {
+            print!("{}", template::DEFAULT);
+            Ok(())
+        }
+        Command::Update { files, opts } => update(files, opts).await,
+        Command::Watch { files, interval, opts } => watch(files, interval, opts).await,
+        Command::Library { watch, unwatch } => library(watch, unwatch),
+    };
+    match res {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {:#}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn fetch(url: String, output: Option<PathBuf>, opts: ExportArgs) -> Result<()> {
+    let export = opts.output.export()?;
+    // attachments can only be kept when there is a folder to put them in
+    let assets = export.format == Format::Markdown && output.is_some();
+    let conv = fetch_and_convert(url, opts.relays(), assets, Progress::none()).await?;
+    let docs = export.docs(conv);
+
+    let Some(path) = output else {
+        if docs.len() > 1 {
+            anyhow::bail!("--branches files needs --output");
+        }
+        std::io::stdout().write_all(&render(&export, &docs[0])?)?;
+        return Ok(());
+    };
+    for target in save(&export, &docs, &path)? {
+        eprintln!("saved {}", target.display());
+    }
+    Ok(())
+}
+
+async fn update(files: Vec<PathBuf>, opts: ExportArgs) -> Result<()> {
+    let export = opts.output.export()?;
+    let entries = library::load();
+    let mut failed = 0;
+    for file in &files {
+        let path = std::path::absolute(file)?;
+        let result = match entries.iter().find(|e| e.path == path) {
+            Some(entry) => sync::update(entry.clone(), export.clone(), opts.relays()).await,
+            None => Err(anyhow::anyhow!("not in the library (see `kraken library`)")),
+        };
+        match result {
+            Ok(added) => println!("ok\t{}\t{} new turns", file.display(), added),
+            Err(e) => {
+                failed += 1;
+                println!("error\t{}\t{:#}", file.display(), e);
+            }
+        }
+    }
+    if failed > 0 {
+        anyhow::bail!("{} of {} files failed", failed, files.len());
+    }
+    Ok(())
+}
+
+fn library(watch: Vec<PathBuf>, unwatch: Vec<PathBuf>) -> Result<()> {
+    if !watch.is_empty() || !unwatch.is_empty() {
+        for path in &watch {
+            library::set_watched(path, true)?;
+        }
+        for path in &unwatch {
+            library::set_watched(path, false)?;
+        }
+        return Ok(());
+    }
+    for entry in library::load() {
+        let watched = if entry.watched { "watched" } else { "" };
+        let path = entry.path.display();
+        let format = entry.format.extension();
+        println!("{}\t{}\t{}\t{}\t{}\t{}", entry.date(), format, entry.title, path, entry.source, watched);
+    }
+    Ok(())
+}
+
+async fn watch(files: Vec<PathBuf>, interval: Option<u64>, opts: ExportArgs) -> Result<()> {
+    let export = opts.output.export()?;
+    let files = files.iter().map(std::path::absolute).collect::<std::io::Result<Vec<_>>>()?;
+    let mut schedule = watch::Schedule::new(interval.unwrap_or_else(|| Settings::load().watch_minutes));
+    let mut warned = None;
+    loop {
+        // read every round, so files marked or unmarked meanwhile are picked up
+        let entries: Vec<_> = library::load()
+            .into_iter()
+            .filter(|e| if files.is_empty() { e.watched } else { files.contains(&e.path) })
+            .collect();
+        // files can be added or marked again meanwhile, so this only warns, once
+        let trouble = match files.iter().find(|f| !entries.iter().any(|e| e.path == **f)) {
+            Some(missing) => Some(format!("{} is not in the library (see `kraken library`)", missing.display())),
+            None if entries.is_empty() => {
+                Some("nothing to watch (pass files or mark them with `kraken library --watch FILE`)".to_string())
+            }
+            None => None,
+        };
+        if trouble != warned {
+            if let Some(trouble) = &trouble {
+                eprintln!("warning: {}", trouble);
+            }
+            warned = trouble;
+        }
+        if entries.is_empty() {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            continue;
+        }
+        let due: Vec<_> = entries.into_iter().filter(|e| schedule.due(&e.path)).collect();
+        for entry in due {
+            let path = entry.path.clone();
+            let result = sync::update(entry, export.clone(), opts.relays()).await;
+            let wait = schedule.done(&path, result.is_ok());
+            match result {
+                Ok(0) => {}
+                Ok(added) => println!("updated\t{}\t{} new turns", path.display(), added),
+                Err(e) => eprintln!("error\t{}\t{:#} (next try in {} min)", path.display(), e, wait.as_secs() / 60),
+            }
+        }
+        let wait = schedule.next_due().map(|at| at.saturating_duration_since(Instant::now())).unwrap_or_default();
+        tokio::time::sleep(wait.max(Duration::from_secs(1))).await;
+    }
+}
+
+async fn run_batch(
+    mut urls: Vec<String>,
+    input: Option<PathBuf>,
+    dir: PathBuf,
+    jobs: usize,
+    opts: ExportArgs,
+) -> Result<()> {
+    if let Some(path) = input {
+        urls.extend(batch::read_url_file(&path)?);
+    }
+    if urls.is_empty() {
+        anyhow::bail!("no URLs given (pass them as arguments or with --input)");
+    }
+    std::fs::create_dir_all(&dir)?;
+
+    let total = urls.len();
+    let job = JobOptions { dir, export: opts.output.export()?, relays: opts.relays(), lang: Lang::En };
+    let permits = Arc::new(Semaphore::new(jobs.max(1)));
+    let mut set = JoinSet::new();
+    for url in urls {
+        set.spawn(batch::job(url, job.clone(), permits.clone()));
+    }
+
+    let mut failed = 0;
+    while let Some(done) = set.join_next().await {
+        let done = done?;
+        match done.result {
+            Ok(paths) => {
+                let paths: Vec<String> = paths.iter().map(|p| p.display().to_string()).collect();
+                println!("ok\t{}\t{}", done.url, paths.join(", "));
+            }
+            Err(e) => {
+                failed += 1;
+                println!("error\t{}\t{}", done.url, e);
+            }
+        }
+    }
+    if failed > 0 {
+        anyhow::bail!("{} of {} links failed", failed, total);
+    }
+    Ok(())
+}
+
+fn run_import(path: PathBuf, list: bool, pick: Vec<String>, dir: PathBuf, export: Export) -> Result<()> {
+    let convs = import::read(&path)?;
+    if list {
+        for (i, conv) in convs.iter().enumerate() {
+            println!("{}\t{}\t{}\t{}", i + 1, import::date(conv), import::conversation_id(conv), conv.title);
+        }
+        return Ok(());
+    }
+    let convs = if pick.is_empty() { convs } else { import::select(convs, &pick)? };
+    if convs.is_empty() {
+        anyhow::bail!("the export has no conversations");
+    }
+    std::fs::create_dir_all(&dir)?;
+
+    let total = convs.len();
+    let mut failed = 0;
+    for (title, result) in import::save_all(&path, &convs, &dir, &export) {
+        match result {
+            Ok(paths) => {
+                let paths: Vec<String> = paths.iter().map(|p| p.display().to_string()).collect();
+                println!("ok\t{}\t{}", title, paths.join(", "));
+            }
+            Err(e) => {
+                failed += 1;
+                println!("error\t{}\t{:#}", title, e);
+            }
+        }
+    }
+    if failed > 0 {
+        anyhow::bail!("{} of {} conversations failed", failed, total);
+    }
+    Ok(())
+}
@@ -0,0 +1,406 @@
+use crate::i18n::Lang;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::sync::Arc;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    Assistant,
+    Tool,
+    System,
+}
+
+impl Role {
+    pub const ALL: [Role; 4] = [Role::User, Role::Assistant, Role::Tool, Role::System];
+
+    // "user", "assistant", ... as in the share payload
+    pub fn id(&self) -> &'static str {
+        match self {
+            Role::User => "user",
+            Role::Assistant => "assistant",
+            Role::Tool => "tool",
+            Role::System => "system",
+        }
+    }
+
+    pub fn label(&self, lang: Lang) -> &'static str {
+        match self {
+            Role::User => lang.tr("out.user"),
+            Role::Assistant => lang.tr("out.assistant"),
+            Role::Tool => lang.tr("out.tool"),
+            Role::System => lang.tr("out.system"),
+        }
+    }
+}
+
+// Turns besides the chat itself, each of which can be left out of an export.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Category {
+    // code interpreter: executed code and its output
+    Code,
+    // web search and browsing: queries and the pages/quotes they returned
+    Browsing,
+    // any other tool (image generation, plugins, ...)
+    Tools,
+    // system messages shown in the conversation
+    System,
+}
+
+impl std::fmt::Display for Category {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Category::Code => write!(f, "code"),
+            Category::Browsing => write!(f, "browsing"),
+            Category::Tools => write!(f, "tools"),
+            Category::System => write!(f, "system"),
+        }
+    }
+}
+
+impl std::str::FromStr for Category {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "code" => Ok(Category::Code),
+            "browsing" => Ok(Category::Browsing),
+            "tools" => Ok(Category::Tools),
+            "system" => Ok(Category::System),
+            s => Err(format!("unknown section '{}' (expected code, browsing, tools or system)", s)),
+        }
+    }
+}
+
+// The assistant calling a tool (`output == false`, text is the call: code, a query,
+// JSON arguments) or the tool answering.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ToolUse {
+    pub name: String,
+    pub category: Category,
+    pub output: bool,
+    pub language: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Turn {
+    pub role: Role,
+    pub text: String,
+    pub id: Option<String>,
+    // unix seconds, as the backend reports them
+    pub created: Option<f64>,
+    pub model: Option<String>,
+    pub attachments: Vec<Attachment>,
+    pub tool: Option<ToolUse>,
+    // pages the answer cites, numbered from 1 in the order they are first cited
+    pub sources: Vec<WebSource>,
+    pub citations: Vec<Citation>,
+    // Set when this turn was edited/regenerated: the turn itself is variant `number`,
+    // the other siblings (with whatever followed them) are in `alternatives`.
+    pub fork: Option<Fork>,
+}
+
+impl Turn {
+    pub fn new(role: Role, text: String) -> Self {
+        Self {
+            role,
+            text,
+            id: None,
+            created: None,
+            model: None,
+            attachments: Vec::new(),
+            tool: None,
+            sources: Vec::new(),
+            citations: Vec::new(),
+            fork: None,
+        }
+    }
+
+    pub fn category(&self) -> Option<Category> {
+        match (&self.tool, &self.role) {
+            (Some(tool), _) => Some(tool.category),
+            (None, Role::System) => Some(Category::System),
+            _ => None,
+        }
+    }
+
+    // "Assistant → python", "Output of python", "User", ...
+    pub fn label(&self, lang: Lang) -> String {
+        self.label_as(lang, self.role.label(lang))
+    }
+
+    // `label` with the role called `role`.
+    pub fn label_as(&self, lang: Lang, role: &str) -> String {
+        match &self.tool {
+            Some(tool) if tool.output => lang.trf("out.tool_output", &[&tool.name]),
+            Some(tool) => format!("{} → {}", role, tool.name),
+            None => role.to_string(),
+        }
+    }
+
+    // The text as Markdown: code sent to a tool and code interpreter output become
+    // fenced blocks, everything else is Markdown already.
+    pub fn body(&self) -> Cow<'_, str> {
+        let text = self.text.replace("\r\n", "\n");
+        let fenced = |lang: &str| {
+            let fence = if text.contains("```") { "~~~~" } else { "```" };
+            Cow::Owned(format!("{0}{1}\n{2}\n{0}", fence, lang, text.trim_end()))
+        };
+        match &self.tool {
+            Some(tool) if !tool.output => fenced(tool.language.as_deref().unwrap_or("")),
+            Some(tool) if tool.category == Category::Code => fenced(""),
+            _ => Cow::Owned(text),
+        }
+    }
+
+    // `body` with `mark(numbers)` written wherever the text cites its `sources`.
+    pub fn cited_body(&self, mark: impl Fn(&[usize]) -> String) -> Cow<'_, str> {
+        if self.citations.is_empty() || self.tool.is_some() {
+            return self.body();
+        }
+        let mut text = String::new();
+        let mut at = 0;
+        for citation in &self.citations {
+            if citation.offset < at || !self.text.is_char_boundary(citation.offset) {
+                continue;
+            }
+            text.push_str(&self.text[at..citation.offset]);
+            text.push_str(&mark(&citation.sources));
+            at = citation.offset;
+        }
+        text.push_str(&self.text[at..]);
+        Cow::Owned(text.replace("\r\n", "\n"))
+    }
+}
+
+// A web page an answer was built from.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WebSource {
+    pub title: String,
+    pub url: String,
+}
+
+// A citation mark in `Turn::text`: the byte offset it goes at and the 1-based numbers
+// of the `Turn::sources` it refers to.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Citation {
+    pub offset: usize,
+    pub sources: Vec<usize>,
+}
+
+// An image or file the message carries. The provider only names it by `pointer`;
+// `data` is filled once it was downloaded and `path` once it was written next to
+// the export (relative to it).
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Attachment {
+    pub image: bool,
+    pub name: String,
+    pub size: Option<u64>,
+    pub mime: Option<String>,
+    pub pointer: Option<String>,
+    #[serde(skip)]
+    pub data: Option<Arc<Vec<u8>>>,
+    pub path: Option<String>,
+}
+
+impl Attachment {
+    // What stands in for the file when it could not be retrieved.
+    pub fn placeholder(&self, lang: Lang) -> String {
+        let what = lang.tr(if self.image { "out.image_missing" } else { "out.file_missing" });
+        match self.size {
+            Some(size) => format!("[{}: {}, {}]", what, self.name, human_size(size, lang)),
+            None => format!("[{}: {}]", what, self.name),
+        }
+    }
+}
+
+pub fn human_size(bytes: u64, lang: Lang) -> String {
+    match bytes {
+        b if b >= 1 << 20 => lang.trf("out.mb", &[&format!("{:.1}", b as f64 / (1 << 20) as f64)]),
+        b if b >= 1 << 10 => lang.trf("out.kb", &[&b.div_ceil(1 << 10)]),
+        b => lang.trf("out.bytes", &[&b]),
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Fork {
+    pub number: usize,
+    pub total: usize,
+    pub alternatives: Vec<Variant>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Variant {
+    pub number: usize,
+    pub turns: Vec<Turn>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Conversation {
+    pub title: String,
+    pub source: String,
+    pub share_id: Option<String>,
+    pub created: Option<f64>,
+    pub updated: Option<f64>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub fetched_at: OffsetDateTime,
+    // relay the payload came through, `None` when fetched directly
+    pub relay: Option<String>,
+    pub turns: Vec<Turn>,
+}
+
+impl Conversation {
+    pub fn new(title: String, source: String, turns: Vec<Turn>) -> Self {
+        Self {
+            title,
+            source,
+            share_id: None,
+            created: None,
+            updated: None,
+            fetched_at: OffsetDateTime::now_utc(),
+            relay: None,
+            turns,
+        }
+    }
+
+    // YAML front matter for notes and static-site tools. Strings are written as JSON
+    // strings, which YAML reads as double-quoted scalars.
+    pub fn front_matter(&self) -> String {
+        let quote = |s: &str| serde_json::to_string(s).unwrap_or_default();
+        let mut out = String::from("---\n");
+        out.push_str(&format!("title: {}\n", quote(&self.title)));
+        out.push_str(&format!("source_url: {}\n", quote(&self.source)));
+        if let Some(id) = &self.share_id {
+            out.push_str(&format!("share_id: {}\n", quote(id)));
+        }
+        if let Some(t) = self.created.and_then(timestamp) {
+            out.push_str(&format!("create_time: {}\n", t));
+        }
+        if let Some(t) = self.updated.and_then(timestamp) {
+            out.push_str(&format!("update_time: {}\n", t));
+        }
+        let models: Vec<String> = self.models().iter().map(|m| quote(m)).collect();
+        out.push_str(&format!("models: [{}]\n", models.join(", ")));
+        out.push_str(&format!("message_count: {}\n", self.turns.len()));
+        if let Ok(t) = self.fetched_at.format(&Rfc3339) {
+            out.push_str(&format!("fetched_at: {}\n", t));
+        }
+        out.push_str("---\n\n");
+        out
+    }
+
+    // Models that answered, in order of first appearance, alternative branches included.
+    pub fn models(&self) -> Vec<String> {
+        let mut models = Vec::new();
+        collect_models(&self.turns, &mut models);
+        models
+    }
+
+    // Every attachment, in alternative branches too.
+    pub fn attachments_mut(&mut self, f: &mut impl FnMut(&mut Attachment)) {
+        visit_attachments(&mut self.turns, f);
+    }
+
+    // Without the turns of the `hidden` categories. A dropped turn's fork moves to the
+    // next turn that is kept.
+    pub fn hide(&self, hidden: &[Category]) -> Conversation {
+        Conversation { turns: hide_turns(&self.turns, hidden), ..self.clone() }
+    }
+
+    pub fn has_branches(&self) -> bool {
+        self.turns.iter().any(|t| t.fork.is_some())
+    }
+
+    // Only the thread the author shared, without alternative branches.
+    pub fn current_thread(&self) -> Conversation {
+        Conversation {
+            turns: self.turns.iter().map(|t| Turn { fork: None, ..t.clone() }).collect(),
+            ..self.clone()
+        }
+    }
+
+    // Every branch as its own linear conversation, the shared thread first.
+    pub fn branches(&self) -> Vec<Conversation> {
+        linearize(&self.turns)
+            .into_iter()
+            .map(|turns| Conversation { turns, ..self.clone() })
+            .collect()
+    }
+}
+
+fn collect_models(turns: &[Turn], models: &mut Vec<String>) {
+    for turn in turns {
+        if let Some(model) = &turn.model
+            && !models.contains(model)
+        {
+            models.push(model.clone());
+        }
+        for variant in turn.fork.iter().flat_map(|f| &f.alternatives) {
+            collect_models(&variant.turns, models);
+        }
+    }
+}
+
+// Unix seconds as RFC 3339, e.g. "2024-05-01T08:00:00Z".
+pub fn timestamp(secs: f64) -> Option<String> {
+    OffsetDateTime::from_unix_timestamp_nanos((secs * 1e9) as i128)
+        .ok()?
+        .format(&Rfc3339)
+        .ok()
+}
+
+fn hide_turns(turns: &[Turn], hidden: &[Category]) -> Vec<Turn> {
+    let mut out = Vec::new();
+    let mut pending = None;
+    for turn in turns {
+        let mut fork = turn.fork.clone().map(|mut fork| {
+            for variant in &mut fork.alternatives {
+                variant.turns = hide_turns(&variant.turns, hidden);
+            }
+            fork
+        });
+        if turn.category().is_some_and(|c| hidden.contains(&c)) {
+            pending = pending.or(fork);
+            continue;
+        }
+        if fork.is_none() {
+            fork = pending.take();
+        }
+        out.push(Turn { fork, ..turn.clone() });
+    }
+    out
+}
+
+fn visit_attachments(turns: &mut [Turn], f: &mut impl FnMut(&mut Attachment)) {
+    for turn in turns {
+        turn.attachments.iter_mut().for_each(&mut *f);
+        for variant in turn.fork.iter_mut().flat_map(|fork| &mut fork.alternatives) {
+            visit_attachments(&mut variant.turns, f);
+        }
+    }
+}
+
+// Relative link target; spaces and parentheses would end a Markdown link early.
+pub fn link(path: &str) -> String {
+    path.replace(' ', "%20").replace('(', "%28").replace(')', "%29")
+}
+
+fn linearize(turns: &[Turn]) -> Vec<Vec<Turn>> {
+    let main: Vec<Turn> = turns.iter().map(|t| Turn { fork: None, ..t.clone() }).collect();
+    let mut out = vec![main.clone()];
+    for (i, turn) in turns.iter().enumerate() {
+        let Some(fork) = &turn.fork else { continue };
+        for variant in &fork.alternatives {
+            for tail in linearize(&variant.turns) {
+                let mut path = main[..i].to_vec();
+                path.extend(tail);
+                out.push(path);
+            }
+        }
+    }
+    out
+}
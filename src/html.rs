@@ -0,0 +1,170 @@
+use crate::conversation::{link, Conversation, Role, Turn};
+use crate::i18n::Lang;
+use pulldown_cmark::{html, CodeBlockKind, CowStr, Event, Options, Parser, Tag, TagEnd};
+use std::sync::OnceLock;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::html::highlighted_html_for_string;
+use syntect::parsing::SyntaxSet;
+
+const CSS: &str = r#"
+body { margin: 0; background: #f4f5f7; color: #1f2328; font: 15px/1.55 -apple-system, "Segoe UI", "Noto Sans", Roboto, Arial, sans-serif; }
+main { max-width: 860px; margin: 0 auto; padding: 24px 16px 48px; }
+h1 { font-size: 1.6em; margin: 0 0 4px; }
+.source { color: #656d76; font-size: .9em; margin-bottom: 24px; word-break: break-all; }
+.turn { margin: 14px 0; padding: 10px 16px; border-radius: 12px; background: #fff; box-shadow: 0 1px 2px rgba(0,0,0,.08); overflow-wrap: anywhere; }
+.turn.user { margin-left: 15%; background: #e7f0ff; }
+.turn.assistant { margin-right: 5%; }
+.turn.call, .turn.tool, .turn.system { margin-right: 5%; background: #f6f8fa; box-shadow: none; border: 1px dashed #d0d7de; font-size: .92em; }
+.role { font-weight: 600; font-size: .85em; color: #656d76; margin-bottom: 4px; }
+.turn pre { padding: 10px 12px; border-radius: 8px; overflow-x: auto; font: 13px/1.45 ui-monospace, "DejaVu Sans Mono", Consolas, Menlo, monospace; }
+.turn code { font-family: ui-monospace, "DejaVu Sans Mono", Consolas, Menlo, monospace; font-size: .92em; }
+.turn :not(pre) > code { background: rgba(0,0,0,.06); padding: 1px 4px; border-radius: 4px; }
+.turn table { border-collapse: collapse; } .turn th, .turn td { border: 1px solid #d0d7de; padding: 4px 8px; }
+.attachment { color: #656d76; font-style: italic; }
+.sources { font-size: 0.9em; color: #656d76; }
+.sources ol { margin: 0.25em 0; }
+details.variant { margin: 8px 0 8px 24px; padding: 4px 12px; border-left: 3px solid #d0d7de; }
+details.variant > summary { cursor: pointer; color: #656d76; font-size: .9em; }
+"#;
+
+pub fn render(conv: &Conversation, lang: Lang) -> String {
+    let mut out = String::new();
+    out.push_str(&format!("<!DOCTYPE html>\n<html lang=\"{}\">\n<head>\n<meta charset=\"utf-8\">\n", lang.code()));
+    out.push_str("<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n");
+    out.push_str(&format!("<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n<main>\n", escape(&conv.title), CSS));
+    out.push_str(&format!("<h1>{}</h1>\n", escape(&conv.title)));
+    out.push_str(&format!("<div class=\"source\">{}: {}</div>\n", lang.tr("out.source"), anchor(&conv.source, &conv.source)));
+    push_turns(&mut out, &conv.turns, lang);
+    out.push_str("</main>\n</body>\n</html>\n");
+    out
+}
+
+fn push_turns(out: &mut String, turns: &[Turn], lang: Lang) {
+    for turn in turns {
+        let class = match turn.role {
+            Role::User => "user",
+            Role::Assistant if turn.tool.is_some() => "assistant call",
+            Role::Assistant => "assistant",
+            Role::Tool => "tool",
+            Role::System => "system",
+        };
+        let label = match &turn.fork {
+            Some(fork) => format!("{} · {}", turn.label(lang), lang.trf("out.variant_inline", &[&fork.number, &fork.total])),
+            None => turn.label(lang),
+        };
+        out.push_str(&format!("<section class=\"turn {}\">\n<div class=\"role\">{}</div>\n", class, escape(&label)));
+        out.push_str(&markdown_to_html(&turn.cited_body(|n| {
+            n.iter().map(|k| format!("[[{}]]({})", k, link(&turn.sources[k - 1].url))).collect()
+        })));
+        for attachment in &turn.attachments {
+            out.push_str(&format!("<p class=\"attachment\">{}</p>\n", escape(&attachment.placeholder(lang))));
+        }
+        if !turn.sources.is_empty() {
+            out.push_str(&format!("<div class=\"sources\">{}:\n<ol>\n", lang.tr("out.sources")));
+            for source in &turn.sources {
+                out.push_str(&format!("<li>{}</li>\n", anchor(&source.url, &source.title)));
+            }
+            out.push_str("</ol>\n</div>\n");
+        }
+        out.push_str("</section>\n");
+        if let Some(fork) = &turn.fork {
+            for variant in &fork.alternatives {
+                out.push_str(&format!(
+                    "<details class=\"variant\">\n<summary>{}</summary>\n",
+                    lang.trf("out.variant", &[&variant.number, &fork.total])
+                ));
+                push_turns(out, &variant.turns, lang);
+                out.push_str("</details>\n");
+            }
+        }
+    }
+}
+
+// Message text is Markdown. Raw HTML inside it is shown as text, never interpreted,
+// links and images that aren't `safe_url`s become their text, and fenced code is
+// highlighted here so the page needs no scripts.
+fn markdown_to_html(text: &str) -> String {
+    let parser = Parser::new_ext(text, Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH);
+    let mut events = Vec::new();
+    let mut code: Option<(String, String)> = None;
+    // for each open link or image, whether it was dropped
+    let mut dropped = Vec::new();
+    for event in parser {
+        match (event, code.as_mut()) {
+            (Event::Start(Tag::Link { ref dest_url, .. } | Tag::Image { ref dest_url, .. }), _)
+                if !safe_url(dest_url) =>
+            {
+                dropped.push(true)
+            }
+            (Event::Start(tag @ (Tag::Link { .. } | Tag::Image { .. })), _) => {
+                dropped.push(false);
+                events.push(Event::Start(tag));
+            }
+            (Event::End(end @ (TagEnd::Link | TagEnd::Image)), _) => {
+                if !dropped.pop().unwrap_or(false) {
+                    events.push(Event::End(end));
+                }
+            }
+            (Event::Start(Tag::CodeBlock(kind)), _) => {
+                let lang = match kind {
+                    CodeBlockKind::Fenced(lang) => lang.split_whitespace().next().unwrap_or("").to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                code = Some((lang, String::new()));
+            }
+            (Event::End(TagEnd::CodeBlock), Some((lang, body))) => {
+                events.push(Event::Html(CowStr::from(highlight(body, lang))));
+                code = None;
+            }
+            (Event::Text(t), Some((_, body))) => body.push_str(&t),
+            (Event::Html(h) | Event::InlineHtml(h), _) => events.push(Event::Text(h)),
+            (event, _) => events.push(event),
+        }
+    }
+    let mut out = String::new();
+    html::push_html(&mut out, events.into_iter());
+    out
+}
+
+// Web and mail links and relative paths (the attachment folder); `javascript:`, `data:`
+// and the like are not followed from a saved page.
+fn safe_url(url: &str) -> bool {
+    // browsers ignore whitespace and control characters inside the scheme
+    let url: String = url.chars().filter(|c| !c.is_ascii_whitespace() && !c.is_control()).collect();
+    let Some((scheme, _)) = url.split_once(':') else {
+        return true;
+    };
+    if scheme.contains(['/', '?', '#']) {
+        return true;
+    }
+    ["http", "https", "mailto"].iter().any(|s| scheme.eq_ignore_ascii_case(s))
+}
+
+// A link to `url`, or just the text when the address isn't safe to follow.
+fn anchor(url: &str, label: &str) -> String {
+    if safe_url(url) {
+        format!("<a href=\"{}\">{}</a>", escape(url), escape(label))
+    } else {
+        escape(label)
+    }
+}
+
+fn highlight(code: &str, lang: &str) -> String {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    static THEME: OnceLock<Theme> = OnceLock::new();
+    let syntaxes = SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines);
+    let theme = THEME.get_or_init(|| ThemeSet::load_defaults().themes["InspiredGitHub"].clone());
+
+    let syntax = syntaxes
+        .find_syntax_by_token(lang)
+        .unwrap_or_else(|| syntaxes.find_syntax_plain_text());
+    highlighted_html_for_string(code, syntaxes, syntax, theme)
+        .unwrap_or_else(|_| format!("<pre><code>{}</code></pre>\n", escape(code)))
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
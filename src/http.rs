@@ -0,0 +1,109 @@
+use crate::progress::{Event, Progress};
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use std::time::Duration;
+
+// A request that gets no answer (or no further bytes) for this long has failed; the
+// next relay is tried.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+// ... and so has one still running after this long, however slowly the bytes come.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(90);
+// Fetching a conversation and its attachments, over every relay and fallback, gives up
+// after this long.
+pub const TOTAL_TIMEOUT: Duration = Duration::from_secs(180);
+const RETRIES: u32 = 3;
+// 429s asking for a longer pause than this are treated as failures
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+pub fn client() -> reqwest::Result<Client> {
+    Client::builder()
+        .user_agent("Mozilla/5.0")
+        .connect_timeout(Duration::from_secs(10))
+        .read_timeout(IDLE_TIMEOUT)
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+}
+
+// Sends `request`, trying again on errors that usually go away by themselves: 5xx,
+// 429 (after its Retry-After) and dropped connections. Waits 1, 2, 4 s between tries.
+// The last response is returned whatever its status.
+pub async fn send(request: RequestBuilder) -> reqwest::Result<Response> {
+    let mut attempt = 0;
+    loop {
+        let Some(this) = request.try_clone().filter(|_| attempt < RETRIES) else {
+            return request.send().await;
+        };
+        let wait = match this.send().await {
+            Ok(resp) if resp.status() == StatusCode::TOO_MANY_REQUESTS => match retry_after(&resp) {
+                Some(wait) if wait > MAX_RETRY_AFTER => return Ok(resp),
+                Some(wait) => wait,
+                None => backoff(attempt),
+            },
+            Ok(resp) if resp.status().is_server_error() => backoff(attempt),
+            Err(e) if dropped(&e) => backoff(attempt),
+            result => return result,
+        };
+        tokio::time::sleep(wait).await;
+        attempt += 1;
+    }
+}
+
+// The body as text, decoded with the charset the server names (UTF-8 otherwise) like
+// `Response::text` does.
+pub async fn text(resp: Response, progress: &Progress) -> reqwest::Result<String> {
+    let charset = resp
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').find_map(|p| p.trim().strip_prefix("charset=")))
+        .map(|c| c.trim_matches('"').to_string());
+    let body = bytes(resp, progress).await?;
+    let encoding = charset.and_then(|c| encoding_rs::Encoding::for_label(c.as_bytes())).unwrap_or(encoding_rs::UTF_8);
+    Ok(encoding.decode(&body).0.into_owned())
+}
+
+// The body, read chunk by chunk so that `progress` can follow large ones.
+pub async fn bytes(mut resp: Response, progress: &Progress) -> reqwest::Result<Vec<u8>> {
+    let total = resp.content_length();
+    let mut body = Vec::new();
+    let mut reported = 0;
+    progress.send(Event::Bytes(0, total));
+    while let Some(chunk) = resp.chunk().await? {
+        body.extend_from_slice(&chunk);
+        // every 64 KB is plenty for a progress bar
+        if body.len() - reported >= 1 << 16 {
+            progress.send(Event::Bytes(body.len() as u64, total));
+            reported = body.len();
+        }
+    }
+    progress.send(Event::Bytes(body.len() as u64, total));
+    Ok(body)
+}
+
+fn backoff(attempt: u32) -> Duration {
+    Duration::from_secs(1 << attempt)
+}
+
+// Only the seconds form; an HTTP date falls back to the usual backoff.
+fn retry_after(resp: &Response) -> Option<Duration> {
+    let value = resp.headers().get(reqwest::header::RETRY_AFTER)?.to_str().ok()?;
+    value.trim().parse().ok().map(Duration::from_secs)
+}
+
+// The connection was reset or closed halfway; timeouts are not retried, a relay that
+// hangs once tends to hang again.
+fn dropped(error: &reqwest::Error) -> bool {
+    use std::io::ErrorKind;
+    let mut source = std::error::Error::source(error);
+    while let Some(e) = source {
+        if let Some(io) = e.downcast_ref::<std::io::Error>()
+            && matches!(
+                io.kind(),
+                ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted | ErrorKind::BrokenPipe | ErrorKind::UnexpectedEof
+            )
+        {
+            return true;
+        }
+        source = e.source();
+    }
+    false
+}
@@ -0,0 +1,211 @@
+use regex::Regex;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+// Share pages are server-rendered; the data behind them is shipped to the browser in
+// inline scripts so the client can hydrate. Depending on the deploy that is a JSON
+// script (`__NEXT_DATA__`), a `window.__remixContext = {...}` assignment, or React
+// Router's turbo-stream pushed through `streamController.enqueue("...")`. Every JSON
+// document found that way is returned, in page order.
+pub fn documents(html: &str) -> Vec<Value> {
+    let scripts = Regex::new(r"(?is)<script\b[^>]*>(.*?)</script>").unwrap();
+    let enqueue = Regex::new(r#"\.enqueue\(\s*("(?:[^"\\]|\\.)*")\s*\)"#).unwrap();
+    let assignment = Regex::new(r"window\.__\w+\s*=\s*").unwrap();
+
+    let mut out = Vec::new();
+    let mut stream = String::new();
+    for script in scripts.captures_iter(html) {
+        let body = script[1].trim();
+        if body.is_empty() {
+            continue;
+        }
+        let chunks: Vec<String> = enqueue
+            .captures_iter(body)
+            .filter_map(|c| serde_json::from_str::<String>(&c[1]).ok())
+            .collect();
+        if !chunks.is_empty() {
+            stream.extend(chunks);
+            continue;
+        }
+        if let Ok(doc) = serde_json::from_str::<Value>(body) {
+            out.push(doc);
+            continue;
+        }
+        for m in assignment.find_iter(body) {
+            let mut de = serde_json::Deserializer::from_str(&body[m.end()..]).into_iter::<Value>();
+            if let Some(Ok(doc)) = de.next() {
+                out.push(doc);
+            }
+        }
+    }
+    if let Some(doc) = decode_turbo_stream(&stream) {
+        out.push(doc);
+    }
+    out
+}
+
+// The first object anywhere in `doc` for which `wanted` holds, depth first.
+pub fn find<'a>(doc: &'a Value, wanted: &dyn Fn(&Map<String, Value>) -> bool) -> Option<&'a Value> {
+    match doc {
+        Value::Object(map) if wanted(map) => Some(doc),
+        Value::Object(map) => map.values().find_map(|v| find(v, wanted)),
+        Value::Array(items) => items.iter().find_map(|v| find(v, wanted)),
+        _ => None,
+    }
+}
+
+// turbo-stream flattens the value into one array: every value sits at an index and
+// objects/arrays hold indices instead of values. Objects are `{"_<key index>": <value
+// index>}`, arrays whose first element is a string are tagged types (dates, promises,
+// maps, ...), negative indices are constants. Deferred values arrive on later lines as
+// `P<id>:<json>`, extending the same array.
+fn decode_turbo_stream(stream: &str) -> Option<Value> {
+    let mut lines = stream.lines().filter(|l| !l.trim().is_empty());
+    let Ok(Value::Array(mut values)) = serde_json::from_str::<Value>(lines.next()?) else {
+        return None;
+    };
+    let mut resolved = HashMap::new();
+    for line in lines {
+        let Some((id, json)) = line.strip_prefix('P').and_then(|l| l.split_once(':')) else {
+            continue;
+        };
+        let Ok(id) = id.parse::<i64>() else { continue };
+        match serde_json::from_str::<Value>(json) {
+            Ok(Value::Number(n)) => {
+                if let Some(index) = n.as_i64() {
+                    resolved.insert(id, index);
+                }
+            }
+            Ok(Value::Array(more)) if !more.is_empty() => {
+                resolved.insert(id, values.len() as i64);
+                values.extend(more);
+            }
+            _ => {}
+        }
+    }
+    let turbo = Turbo { values, resolved };
+    Some(turbo.hydrate(0, 0))
+}
+
+struct Turbo {
+    values: Vec<Value>,
+    resolved: HashMap<i64, i64>,
+}
+
+impl Turbo {
+    fn hydrate(&self, index: i64, depth: usize) -> Value {
+        // indices may be shared, and a malformed stream could loop
+        if index < 0 || depth > 256 {
+            return Value::Null;
+        }
+        let Some(value) = self.values.get(index as usize) else {
+            return Value::Null;
+        };
+        match value {
+            Value::Object(map) => Value::Object(self.object(map, depth)),
+            Value::Array(items) => match items.first() {
+                Some(Value::String(tag)) => self.tagged(tag, &items[1..], depth),
+                _ => Value::Array(items.iter().map(|i| self.at(i, depth)).collect()),
+            },
+            primitive => primitive.clone(),
+        }
+    }
+
+    fn at(&self, index: &Value, depth: usize) -> Value {
+        index.as_i64().map_or(Value::Null, |i| self.hydrate(i, depth + 1))
+    }
+
+    fn object(&self, map: &Map<String, Value>, depth: usize) -> Map<String, Value> {
+        map.iter()
+            .filter_map(|(k, v)| {
+                let key = self.values.get(k.strip_prefix('_')?.parse::<usize>().ok()?)?.as_str()?;
+                Some((key.to_string(), self.at(v, depth)))
+            })
+            .collect()
+    }
+
+    fn tagged(&self, tag: &str, args: &[Value], depth: usize) -> Value {
+        match (tag, args) {
+            // dates as epoch milliseconds; URLs and bigints as their string form
+            ("D" | "U" | "B", [v, ..]) => v.clone(),
+            ("P" | "Z", [id, ..]) => match id.as_i64().and_then(|id| self.resolved.get(&id)) {
+                Some(&index) => self.hydrate(index, depth + 1),
+                None => Value::Null,
+            },
+            ("S", items) => Value::Array(items.iter().map(|i| self.at(i, depth)).collect()),
+            ("M", pairs) => Value::Object(
+                pairs
+                    .chunks(2)
+                    .filter_map(|kv| match (self.at(&kv[0], depth), kv.get(1)) {
+                        (Value::String(k), Some(v)) => Some((k, self.at(v, depth))),
+                        _ => None,
+                    })
+                    .collect(),
+            ),
+            ("N", [Value::Object(map), ..]) => Value::Object(self.object(map, depth)),
+            _ => Value::Null,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::documents;
+    use crate::share::SharePayload;
+
+    const USER: &str = r#"{"author":{"role":"user"},"content":{"content_type":"text","parts":["Hi"]}}"#;
+    const ASSISTANT: &str = r#"{"author":{"role":"assistant"},"content":{"content_type":"text","parts":["Hello"]}}"#;
+
+    fn texts(html: &str) -> Vec<String> {
+        let payload = SharePayload::from_page(html).expect("no conversation in the page");
+        payload.turns().into_iter().map(|t| t.text).collect()
+    }
+
+    #[test]
+    fn reads_next_data() {
+        let html = format!(
+            r#"<html><script id="__NEXT_DATA__" type="application/json">{{"props":{{"pageProps":{{"serverResponse":{{"data":{{
+                "current_node":"b","mapping":{{
+                "a":{{"id":"a","message":{USER},"children":["b"]}},
+                "b":{{"id":"b","message":{ASSISTANT},"parent":"a"}}}}}}}}}}}}}}</script></html>"#
+        );
+        assert_eq!(texts(&html), ["Hi", "Hello"]);
+    }
+
+    #[test]
+    fn reads_window_assignment() {
+        let html = format!(
+            r#"<script>window.__remixContext = {{"state":{{"loaderData":{{"routes/share":{{"data":{{
+                "linear_conversation":[{{"id":"a","message":{USER}}},{{"id":"b","message":{ASSISTANT}}}]}}}}}}}}}};
+                __remixContext.p = function() {{}};</script>"#
+        );
+        assert_eq!(texts(&html), ["Hi", "Hello"]);
+    }
+
+    #[test]
+    fn decodes_turbo_stream() {
+        // the first line and a deferred value, enqueued from separate scripts
+        let html = concat!(
+            r#"<script>window.__reactRouterContext.streamController.enqueue("#,
+            r#""[{\"_1\":2},\"data\",{\"_3\":4,\"_5\":6},\"title\",\"Shared\",\"linear_conversation\",[\"P\",7]]\n");</script>"#,
+            r#"<script>window.__reactRouterContext.streamController.enqueue("#,
+            r#""P7:[[8,27],{\"_20\":21,\"_26\":9},{\"_20\":21,\"_22\":10,\"_23\":11,\"_24\":-5,\"_25\":19},{\"_13\":14},"#,
+            r#"{\"_16\":17,\"_18\":12},[15],\"role\",\"user\",\"Hi\",\"content_type\",\"text\",\"parts\",[\"D\",1717000000],"#,
+            r#"\"id\",\"n1\",\"author\",\"content\",\"end_turn\",\"create_time\",\"message\",{\"_20\":35,\"_26\":28},"#,
+            r#"{\"_20\":35,\"_22\":29,\"_23\":30,\"_24\":-5,\"_25\":34},{\"_13\":32},{\"_16\":17,\"_18\":31},[33],"#,
+            r#"\"assistant\",\"Hello\",[\"D\",1717000000],\"n2\"]\n");</script>"#,
+        );
+        assert_eq!(texts(html), ["Hi", "Hello"]);
+        let payload = SharePayload::from_page(html).unwrap();
+        assert_eq!(payload.title.as_deref(), Some("Shared"));
+        let turns = payload.turns();
+        assert_eq!(turns[1].id.as_deref(), Some("n2"));
+        assert_eq!(turns[1].created, Some(1717000000.0));
+    }
+
+    #[test]
+    fn skips_pages_without_data() {
+        assert!(documents("<script>let x = 1;</script><script></script>").is_empty());
+        assert!(SharePayload::from_page(r#"<script type="application/json">{"props":{}}</script>"#).is_none());
+    }
+}
@@ -0,0 +1,341 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// Languages for the window and, separately, for the labels inside exports. Adding one
+// means a catalog below plus an entry in `ALL`; missing keys fall back to English.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Lang {
+    #[default]
+    En,
+    Ru,
+}
+
+impl Lang {
+    pub const ALL: [Lang; 2] = [Lang::En, Lang::Ru];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Lang::En => "en",
+            Lang::Ru => "ru",
+        }
+    }
+
+    // From LC_ALL / LC_MESSAGES / LANG ("ru_RU.UTF-8" -> Ru); English otherwise.
+    pub fn from_env() -> Lang {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|v| !v.is_empty())
+            .and_then(|v| v.get(..2).and_then(|code| code.parse().ok()))
+            .unwrap_or_default()
+    }
+
+    fn catalog(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Lang::En => EN,
+            Lang::Ru => RU,
+        }
+    }
+
+    pub fn tr(self, key: &str) -> &'static str {
+        let find = |catalog: &'static [(&'static str, &'static str)]| {
+            catalog.iter().find(|(k, _)| *k == key).map(|(_, v)| *v)
+        };
+        find(self.catalog()).or_else(|| find(EN)).unwrap_or_else(|| {
+            debug_assert!(false, "no message '{}'", key);
+            ""
+        })
+    }
+
+    // `tr` with every `{}` replaced by the next argument.
+    pub fn trf(self, key: &str, args: &[&dyn fmt::Display]) -> String {
+        let mut out = self.tr(key).to_string();
+        for arg in args {
+            out = out.replacen("{}", &arg.to_string(), 1);
+        }
+        out
+    }
+}
+
+impl fmt::Display for Lang {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Lang::En => write!(f, "English"),
+            Lang::Ru => write!(f, "Русский"),
+        }
+    }
+}
+
+impl std::str::FromStr for Lang {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "en" | "english" => Ok(Lang::En),
+            "ru" | "russian" => Ok(Lang::Ru),
+            s => Err(format!("unknown language '{}' (expected en or ru)", s)),
+        }
+    }
+}
+
+const EN: &[(&str, &str)] = &[
+    // exports
+    ("out.source", "Source"),
+    ("out.user", "User"),
+    ("out.assistant", "Assistant"),
+    ("out.tool", "Tool"),
+    ("out.system", "System"),
+    ("out.tool_output", "Output of {}"),
+    ("out.variant", "Variant {} of {}"),
+    ("out.variant_inline", "variant {} of {}"),
+    ("out.sources", "Sources"),
+    ("out.image_missing", "Image not available"),
+    ("out.file_missing", "File not available"),
+    ("out.bytes", "{} B"),
+    ("out.kb", "{} KB"),
+    ("out.mb", "{} MB"),
+    // window
+    ("ui.public_link", "Public link:"),
+    ("ui.paste", "Paste"),
+    ("ui.download", "Download"),
+    ("ui.relays", "Relays:"),
+    ("ui.format", "Format:"),
+    ("ui.branches", "Branches:"),
+    ("ui.branches_current", "Shared thread only"),
+    ("ui.branches_inline", "All branches inline"),
+    ("ui.branches_files", "File per branch"),
+    ("ui.include", "Include:"),
+    ("ui.code", "Code & output"),
+    ("ui.browsing", "Browsing"),
+    ("ui.tools", "Other tools"),
+    ("ui.system", "System messages"),
+    ("ui.front_matter", "YAML front matter (Markdown)"),
+    ("ui.citations", "Citations:"),
+    ("ui.citations_footnotes", "Footnotes"),
+    ("ui.citations_links", "Numbered links"),
+    ("ui.math", "Math:"),
+    ("ui.math_keep", "Keep \\( \\)"),
+    ("ui.math_dollars", "$ … $"),
+    ("ui.batch", "Batch (one link per line):"),
+    ("ui.load_file", "Load file..."),
+    ("ui.parallel", "Parallel:"),
+    ("ui.download_all", "Download all"),
+    ("ui.account_export", "Account export:"),
+    ("ui.open_export", "Open export..."),
+    ("ui.all", "All"),
+    ("ui.save_selected", "Save selected..."),
+    ("ui.ui_language", "Interface:"),
+    ("ui.output_language", "Export labels:"),
+    ("ui.template", "Markdown template:"),
+    ("ui.template_default", "Built-in"),
+    ("ui.choose", "Choose..."),
+    ("ui.log", "Log:"),
+    ("ui.log_empty", "(log is empty)"),
+    ("ui.settings", "Settings"),
+    ("ui.back", "Back"),
+    ("ui.theme", "Theme:"),
+    ("ui.theme_dark", "Dark"),
+    ("ui.theme_light", "Light"),
+    ("ui.output_dir", "Save dialogs open in:"),
+    ("ui.output_dir_none", "(system default)"),
+    ("ui.reset", "Reset"),
+    ("ui.naming", "File names:"),
+    ("ui.naming_hint", "{title}, {id}, {date}"),
+    ("ui.settings_file", "Stored in {}"),
+    ("ui.library", "Library"),
+    ("ui.library_empty", "Nothing saved yet"),
+    ("ui.library_file", "Index: {}"),
+    ("ui.open", "Open"),
+    ("ui.reveal", "Show in folder"),
+    ("ui.export_as", "Export as..."),
+    ("ui.delete", "Delete"),
+    ("ui.confirm_delete", "Delete the file?"),
+    ("ui.missing", "(file missing)"),
+    ("ui.update", "Update"),
+    ("ui.cancel", "Cancel"),
+    ("ui.watch", "Watch"),
+    ("ui.watch_minutes", "Check watched files every (minutes):"),
+    // status line and log
+    ("st.enter_link", "Enter a link"),
+    ("st.error", "Error: {}"),
+    ("st.downloading", "Downloading..."),
+    ("st.ready", "Ready. Choose where to save."),
+    ("st.saved", "Saved"),
+    ("st.save_cancelled", "Save cancelled"),
+    ("st.batch_empty", "Add at least one link to the batch"),
+    ("st.batch_cancelled", "Batch cancelled"),
+    ("st.batch_progress", "Batch: {}/{}"),
+    ("st.batch_failed", ", {} failed"),
+    ("st.reading_export", "Reading export..."),
+    ("st.export_count", "Export: {} conversations"),
+    ("st.pick_one", "Tick at least one conversation"),
+    ("st.import_cancelled", "Import cancelled"),
+    ("st.saving_import", "Saving {} conversations..."),
+    ("st.imported", "Imported {} of {}"),
+    ("st.updating", "Updating {}..."),
+    ("st.updated", "Updated: {} new turns added"),
+    ("st.up_to_date", "Already up to date"),
+    ("st.cancelled", "Download cancelled"),
+    ("st.attempt", "{} via {}..."),
+    ("st.stage_api", "Share API ({} of {})"),
+    ("st.stage_page_data", "Share page"),
+    ("st.stage_page_text", "Page text (fallback)"),
+    ("st.stage_attachment", "Attachment {} of {}"),
+    ("log.pasted", "Pasted URL from clipboard"),
+    ("log.loaded_list", "Loaded URL list: {}"),
+    ("log.start_batch", "Start batch: {} links -> {}"),
+    ("log.saved_item", "Saved {} -> {}"),
+    ("log.failed_item", "Failed {}: {}"),
+    ("log.import", "Import: {}"),
+    ("log.export_count", "Export has {} conversations"),
+    ("log.start_download", "Start download: {} (relays: {})"),
+    ("log.branches_dropped", "Conversation has edited/regenerated branches; only the shared thread is kept"),
+    ("log.fetched_via", "Fetched & parsed successfully via {}"),
+    ("log.fetched_direct", "Fetched & parsed successfully (direct)"),
+    ("log.file_saved", "File saved: {}"),
+    ("log.template", "Markdown template: {}"),
+    ("log.deleted", "Deleted {}"),
+    ("log.start_update", "Update {} from {}"),
+    ("log.updated", "Updated {}: {} new turns"),
+    ("log.up_to_date", "{} is up to date"),
+    ("log.watching", "Watching {}"),
+    ("log.unwatched", "Stopped watching {}"),
+    ("log.watch_updated", "Watch: {} new turns in {}"),
+    ("log.watch_failed", "Watch: {} failed, next try in {} min: {}"),
+    ("log.attempt_ok", "{} via {}: ok"),
+    ("log.attempt_failed", "{} via {}: {}"),
+    ("err.no_link", "no link given"),
+    ("err.unsupported", "Unsupported link (supported: {})"),
+    ("err.not_fetched", "Could not fetch the conversation:\n{}"),
+    ("err.timed_out", "gave up after {} s without getting the conversation"),
+];
+
+const RU: &[(&str, &str)] = &[
+    ("out.source", "Источник"),
+    ("out.user", "Пользователь"),
+    ("out.assistant", "Ассистент"),
+    ("out.tool", "Инструмент"),
+    ("out.system", "Система"),
+    ("out.tool_output", "Вывод {}"),
+    ("out.variant", "Вариант {} из {}"),
+    ("out.variant_inline", "вариант {} из {}"),
+    ("out.sources", "Источники"),
+    ("out.image_missing", "Изображение недоступно"),
+    ("out.file_missing", "Файл недоступен"),
+    ("out.bytes", "{} Б"),
+    ("out.kb", "{} КБ"),
+    ("out.mb", "{} МБ"),
+    ("ui.public_link", "Публичная ссылка:"),
+    ("ui.paste", "Вставить"),
+    ("ui.download", "Скачать"),
+    ("ui.relays", "Ретрансляторы:"),
+    ("ui.format", "Формат:"),
+    ("ui.branches", "Ветки:"),
+    ("ui.branches_current", "Только общая ветка"),
+    ("ui.branches_inline", "Все ветки в одном файле"),
+    ("ui.branches_files", "Файл на ветку"),
+    ("ui.include", "Включить:"),
+    ("ui.code", "Код и вывод"),
+    ("ui.browsing", "Поиск в сети"),
+    ("ui.tools", "Другие инструменты"),
+    ("ui.system", "Системные сообщения"),
+    ("ui.front_matter", "YAML-заголовок (Markdown)"),
+    ("ui.citations", "Ссылки на источники:"),
+    ("ui.citations_footnotes", "Сноски"),
+    ("ui.citations_links", "Нумерованные ссылки"),
+    ("ui.math", "Формулы:"),
+    ("ui.math_keep", "Оставить \\( \\)"),
+    ("ui.math_dollars", "$ … $"),
+    ("ui.batch", "Пакет (по ссылке в строке):"),
+    ("ui.load_file", "Из файла..."),
+    ("ui.parallel", "Параллельно:"),
+    ("ui.download_all", "Скачать все"),
+    ("ui.account_export", "Экспорт аккаунта:"),
+    ("ui.open_export", "Открыть экспорт..."),
+    ("ui.all", "Все"),
+    ("ui.save_selected", "Сохранить выбранные..."),
+    ("ui.ui_language", "Интерфейс:"),
+    ("ui.output_language", "Подписи в файлах:"),
+    ("ui.template", "Шаблон Markdown:"),
+    ("ui.template_default", "Встроенный"),
+    ("ui.choose", "Выбрать..."),
+    ("ui.log", "Журнал:"),
+    ("ui.log_empty", "(журнал пуст)"),
+    ("ui.settings", "Настройки"),
+    ("ui.back", "Назад"),
+    ("ui.theme", "Тема:"),
+    ("ui.theme_dark", "Тёмная"),
+    ("ui.theme_light", "Светлая"),
+    ("ui.output_dir", "Папка для сохранения:"),
+    ("ui.output_dir_none", "(по умолчанию)"),
+    ("ui.reset", "Сбросить"),
+    ("ui.naming", "Имена файлов:"),
+    ("ui.naming_hint", "{title}, {id}, {date}"),
+    ("ui.settings_file", "Хранятся в {}"),
+    ("ui.library", "Библиотека"),
+    ("ui.library_empty", "Пока ничего не сохранено"),
+    ("ui.library_file", "Индекс: {}"),
+    ("ui.open", "Открыть"),
+    ("ui.reveal", "Показать в папке"),
+    ("ui.export_as", "Экспорт в..."),
+    ("ui.delete", "Удалить"),
+    ("ui.confirm_delete", "Удалить файл?"),
+    ("ui.missing", "(файл не найден)"),
+    ("ui.update", "Обновить"),
+    ("ui.cancel", "Отмена"),
+    ("ui.watch", "Следить"),
+    ("ui.watch_minutes", "Проверять отслеживаемые файлы каждые (минут):"),
+    ("st.enter_link", "Укажите ссылку"),
+    ("st.error", "Ошибка: {}"),
+    ("st.downloading", "Загрузка..."),
+    ("st.ready", "Готово. Выберите, куда сохранить."),
+    ("st.saved", "Сохранено"),
+    ("st.save_cancelled", "Сохранение отменено"),
+    ("st.batch_empty", "Добавьте в пакет хотя бы одну ссылку"),
+    ("st.batch_cancelled", "Пакет отменён"),
+    ("st.batch_progress", "Пакет: {}/{}"),
+    ("st.batch_failed", ", с ошибкой: {}"),
+    ("st.reading_export", "Чтение экспорта..."),
+    ("st.export_count", "Экспорт: бесед — {}"),
+    ("st.pick_one", "Отметьте хотя бы одну беседу"),
+    ("st.import_cancelled", "Импорт отменён"),
+    ("st.saving_import", "Сохранение бесед: {}..."),
+    ("st.imported", "Импортировано {} из {}"),
+    ("st.updating", "Обновление: {}..."),
+    ("st.updated", "Обновлено: добавлено реплик — {}"),
+    ("st.up_to_date", "Новых реплик нет"),
+    ("st.cancelled", "Загрузка отменена"),
+    ("st.attempt", "{} через {}..."),
+    ("st.stage_api", "API ссылки ({} из {})"),
+    ("st.stage_page_data", "Страница ссылки"),
+    ("st.stage_page_text", "Текст страницы (запасной вариант)"),
+    ("st.stage_attachment", "Вложение {} из {}"),
+    ("log.pasted", "Ссылка вставлена из буфера обмена"),
+    ("log.loaded_list", "Загружен список ссылок: {}"),
+    ("log.start_batch", "Пакет: ссылок — {} -> {}"),
+    ("log.saved_item", "Сохранено {} -> {}"),
+    ("log.failed_item", "Ошибка {}: {}"),
+    ("log.import", "Импорт: {}"),
+    ("log.export_count", "Бесед в экспорте: {}"),
+    ("log.start_download", "Загрузка: {} (ретрансляторы: {})"),
+    ("log.branches_dropped", "В беседе есть изменённые/перегенерированные ветки; сохраняется только общая"),
+    ("log.fetched_via", "Получено и разобрано через {}"),
+    ("log.fetched_direct", "Получено и разобрано (напрямую)"),
+    ("log.file_saved", "Файл сохранён: {}"),
+    ("log.template", "Шаблон Markdown: {}"),
+    ("log.deleted", "Удалён {}"),
+    ("log.start_update", "Обновление {} из {}"),
+    ("log.updated", "Обновлён {}: новых реплик — {}"),
+    ("log.up_to_date", "{}: новых реплик нет"),
+    ("log.watching", "Отслеживается {}"),
+    ("log.unwatched", "Больше не отслеживается {}"),
+    ("log.watch_updated", "Отслеживание: новых реплик — {} в {}"),
+    ("log.watch_failed", "Отслеживание: ошибка для {}, следующая попытка через {} мин: {}"),
+    ("log.attempt_ok", "{} через {}: успешно"),
+    ("log.attempt_failed", "{} через {}: {}"),
+    ("err.no_link", "ссылка не указана"),
+    ("err.unsupported", "Ссылка не поддерживается (поддерживаются: {})"),
+    ("err.not_fetched", "Не удалось получить беседу:\n{}"),
+    ("err.timed_out", "беседа не получена за {} с, загрузка прекращена"),
+];
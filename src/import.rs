@@ -0,0 +1,166 @@
+use crate::conversation::Conversation;
+use crate::share::{self, SharePayload};
+use crate::{batch, save, Export, Format};
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use time::OffsetDateTime;
+
+// Reads the account data export (Settings → Data controls → Export data): either the
+// zip as downloaded or the `conversations.json` inside it. Nothing goes over the network.
+pub fn read(path: &Path) -> Result<Vec<Conversation>> {
+    let body = conversations_json(path).with_context(|| format!("cannot read {}", path.display()))?;
+    let payloads: Vec<SharePayload> =
+        serde_json::from_str(&body).context("conversations.json is not a ChatGPT data export")?;
+    Ok(payloads
+        .into_iter()
+        .filter_map(|p| {
+            let source = match p.conversation_id.as_deref().or(p.id.as_deref()) {
+                Some(id) => format!("https://chatgpt.com/c/{}", id),
+                None => String::new(),
+            };
+            p.into_conversation(source)
+        })
+        .collect())
+}
+
+fn conversations_json(path: &Path) -> Result<String> {
+    let Some(mut archive) = open_zip(path)? else {
+        return Ok(std::fs::read_to_string(path)?);
+    };
+    // usually at the top level, but re-zipped exports sometimes add a folder
+    let name = archive
+        .file_names()
+        .filter(|n| n.rsplit('/').next() == Some("conversations.json"))
+        .min_by_key(|n| n.len())
+        .map(str::to_string)
+        .context("no conversations.json in the archive")?;
+    let mut body = String::new();
+    archive.by_name(&name)?.read_to_string(&mut body)?;
+    Ok(body)
+}
+
+fn open_zip(path: &Path) -> Result<Option<zip::ZipArchive<File>>> {
+    let mut file = File::open(path)?;
+    let mut magic = [0u8; 4];
+    if file.read(&mut magic)? != 4 || magic != *b"PK\x03\x04" {
+        return Ok(None);
+    }
+    Ok(Some(zip::ZipArchive::new(File::open(path)?)?))
+}
+
+// Uploads and generated images sit next to conversations.json in the export, named
+// after their file id ("file-AbC123-photo.jpg").
+enum Files {
+    Zip(zip::ZipArchive<File>),
+    Dir(PathBuf),
+}
+
+impl Files {
+    fn open(export: &Path) -> Result<Files> {
+        Ok(match open_zip(export)? {
+            Some(archive) => Files::Zip(archive),
+            None => match export.parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => Files::Dir(dir.to_path_buf()),
+                _ => Files::Dir(PathBuf::from(".")),
+            },
+        })
+    }
+
+    fn find(&mut self, id: &str) -> Option<Vec<u8>> {
+        // "file-AbC123-photo.jpg", "file-AbC123.png" or "file-AbC123", but not "file-AbC1234.png"
+        let matches = |name: &str| {
+            let rest = name.rsplit('/').next().and_then(|n| n.strip_prefix(id));
+            rest.is_some_and(|r| r.is_empty() || r.starts_with(['-', '.']))
+        };
+        match self {
+            Files::Zip(archive) => {
+                let name = archive.file_names().find(|n| matches(n))?.to_string();
+                let mut data = Vec::new();
+                archive.by_name(&name).ok()?.read_to_end(&mut data).ok()?;
+                Some(data)
+            }
+            Files::Dir(dir) => {
+                let entry = std::fs::read_dir(dir).ok()?.flatten().find(|e| matches(&e.file_name().to_string_lossy()))?;
+                std::fs::read(entry.path()).ok()
+            }
+        }
+    }
+
+    fn load(&mut self, conv: &mut Conversation) {
+        conv.attachments_mut(&mut |a| {
+            if let Some(pointer) = &a.pointer {
+                a.data = self.find(share::file_id(pointer)).map(Arc::new);
+            }
+        });
+    }
+}
+
+// The id in the `chatgpt.com/c/<id>` address the conversation was imported under.
+pub fn conversation_id(conv: &Conversation) -> &str {
+    conv.source.rsplit('/').next().unwrap_or_default()
+}
+
+// "2024-05-01", from the last update (or creation) time; empty when neither is known.
+pub fn date(conv: &Conversation) -> String {
+    conv.updated
+        .or(conv.created)
+        .and_then(|secs| OffsetDateTime::from_unix_timestamp(secs as i64).ok())
+        .map(|t| format!("{}-{:02}-{:02}", t.year(), t.month() as u8, t.day()))
+        .unwrap_or_default()
+}
+
+// Picks by 1-based position in the export or by conversation id (a prefix is enough).
+pub fn select(convs: Vec<Conversation>, picks: &[String]) -> Result<Vec<Conversation>> {
+    let mut chosen = Vec::new();
+    for pick in picks {
+        let pick = pick.trim();
+        let found = match pick.parse::<usize>() {
+            Ok(n) => convs.get(n.wrapping_sub(1)),
+            Err(_) => convs.iter().find(|c| !pick.is_empty() && conversation_id(c).starts_with(pick)),
+        };
+        match found {
+            Some(conv) => chosen.push(conv.clone()),
+            None => anyhow::bail!("no conversation '{}' in the export", pick),
+        }
+    }
+    Ok(chosen)
+}
+
+// Saves every conversation into `dir` under batch-style names. Titles repeat a lot in
+// exports ("New chat"), so clashing names get a counter. Markdown takes the attachments
+// along from `archive`, the export they were read from.
+pub fn save_all(
+    archive: &Path,
+    convs: &[Conversation],
+    dir: &Path,
+    export: &Export,
+) -> Vec<(String, Result<Vec<PathBuf>>)> {
+    let format = &export.format;
+    let mut files = match format {
+        Format::Markdown => Files::open(archive).ok(),
+        _ => None,
+    };
+    let mut taken = HashSet::new();
+    convs
+        .iter()
+        .map(|conv| {
+            let name = batch::file_name(conv, format, &export.naming);
+            let mut path = dir.join(&name);
+            let mut n = 1;
+            while !taken.insert(path.clone()) {
+                n += 1;
+                let stem = name.strip_suffix(&format!(".{}", format.extension())).unwrap_or(&name);
+                path = dir.join(format!("{}-{}.{}", stem, n, format.extension()));
+            }
+            let mut conv = conv.clone();
+            if let Some(files) = files.as_mut() {
+                files.load(&mut conv);
+            }
+            (conv.title.clone(), save(export, &export.docs(conv), &path))
+        })
+        .collect()
+}
@@ -0,0 +1,158 @@
+use crate::conversation::{timestamp, Conversation, Role, Turn};
+use serde::Serialize;
+use time::format_description::well_known::Rfc3339;
+
+// Bump when a field changes meaning or disappears; adding fields keeps the version.
+const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct Document<'a> {
+    schema_version: u32,
+    title: &'a str,
+    source_url: &'a str,
+    share_id: Option<&'a str>,
+    fetched_at: String,
+    relay: Option<&'a str>,
+    create_time: Option<String>,
+    update_time: Option<String>,
+    messages: Vec<Message<'a>>,
+}
+
+#[derive(Serialize)]
+struct Message<'a> {
+    id: Option<&'a str>,
+    role: &'static str,
+    create_time: Option<String>,
+    model: Option<&'a str>,
+    content: Vec<Part<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool: Option<Tool<'a>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    sources: Vec<Source<'a>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    citations: Vec<CitationInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    variant: Option<VariantInfo>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    alternatives: Vec<Alternative<'a>>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Part<'a> {
+    Text { text: &'a str },
+    Attachment {
+        kind: &'static str,
+        name: &'a str,
+        size_bytes: Option<u64>,
+        mime_type: Option<&'a str>,
+        asset_pointer: Option<&'a str>,
+    },
+}
+
+#[derive(Serialize)]
+struct Tool<'a> {
+    name: &'a str,
+    category: String,
+    kind: &'static str,
+    language: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct VariantInfo {
+    number: usize,
+    total: usize,
+}
+
+#[derive(Serialize)]
+struct Source<'a> {
+    number: usize,
+    title: &'a str,
+    url: &'a str,
+}
+
+// `offset` counts characters (code points) of the text part.
+#[derive(Serialize)]
+struct CitationInfo {
+    offset: usize,
+    sources: Vec<usize>,
+}
+
+#[derive(Serialize)]
+struct Alternative<'a> {
+    number: usize,
+    messages: Vec<Message<'a>>,
+}
+
+pub fn render(conv: &Conversation) -> anyhow::Result<String> {
+    let doc = Document {
+        schema_version: SCHEMA_VERSION,
+        title: &conv.title,
+        source_url: &conv.source,
+        share_id: conv.share_id.as_deref(),
+        fetched_at: conv.fetched_at.format(&Rfc3339)?,
+        relay: conv.relay.as_deref(),
+        create_time: conv.created.and_then(timestamp),
+        update_time: conv.updated.and_then(timestamp),
+        messages: messages(&conv.turns),
+    };
+    Ok(serde_json::to_string_pretty(&doc)? + "\n")
+}
+
+fn messages(turns: &[Turn]) -> Vec<Message<'_>> {
+    turns
+        .iter()
+        .map(|turn| Message {
+            id: turn.id.as_deref(),
+            role: match turn.role {
+                Role::User => "user",
+                Role::Assistant => "assistant",
+                Role::Tool => "tool",
+                Role::System => "system",
+            },
+            create_time: turn.created.and_then(timestamp),
+            model: turn.model.as_deref(),
+            content: content(turn),
+            tool: turn.tool.as_ref().map(|t| Tool {
+                name: &t.name,
+                category: t.category.to_string(),
+                kind: if t.output { "output" } else { "call" },
+                language: t.language.as_deref(),
+            }),
+            sources: (1..)
+                .zip(&turn.sources)
+                .map(|(number, s)| Source { number, title: &s.title, url: &s.url })
+                .collect(),
+            citations: turn
+                .citations
+                .iter()
+                .filter_map(|c| {
+                    let offset = turn.text.get(..c.offset)?.chars().count();
+                    Some(CitationInfo { offset, sources: c.sources.clone() })
+                })
+                .collect(),
+            variant: turn.fork.as_ref().map(|f| VariantInfo { number: f.number, total: f.total }),
+            alternatives: turn
+                .fork
+                .iter()
+                .flat_map(|f| &f.alternatives)
+                .map(|v| Alternative { number: v.number, messages: messages(&v.turns) })
+                .collect(),
+        })
+        .collect()
+}
+
+fn content(turn: &Turn) -> Vec<Part<'_>> {
+    let mut parts = Vec::new();
+    if !turn.text.is_empty() {
+        parts.push(Part::Text { text: &turn.text });
+    }
+    parts.extend(turn.attachments.iter().map(|a| Part::Attachment {
+        kind: if a.image { "image" } else { "file" },
+        name: &a.name,
+        size_bytes: a.size,
+        mime_type: a.mime.as_deref(),
+        asset_pointer: a.pointer.as_deref(),
+    }));
+    parts
+}
@@ -0,0 +1,224 @@
+use crate::conversation::{Category, Conversation};
+use crate::i18n::Lang;
+use crate::{Branches, Citations, Export, Format, Math};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+// Every file Kraken has saved, in `<data dir>/kraken/library.json`
+// (`$XDG_DATA_HOME/kraken` on Linux). Next to it, `snapshots/` keeps a copy of each
+// saved conversation so it can be exported again without fetching it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Entry {
+    pub title: String,
+    pub source: String,
+    // RFC 3339, when the file was written
+    pub saved_at: String,
+    pub path: PathBuf,
+    pub format: Format,
+    // checked for new turns now and then (see `watch`)
+    #[serde(default)]
+    pub watched: bool,
+    // the folder Markdown attachments were written into, when there were any
+    #[serde(default)]
+    pub files: Option<PathBuf>,
+    // the Markdown template it was written with; the built-in one when unset
+    #[serde(default)]
+    pub template: Option<PathBuf>,
+    // the rest of the options it was written with, for updates; unset in entries of
+    // older versions, which go with the current settings
+    #[serde(default)]
+    pub branches: Option<Branches>,
+    #[serde(default)]
+    pub hidden: Option<Vec<Category>>,
+    #[serde(default)]
+    pub lang: Option<Lang>,
+    #[serde(default)]
+    pub citations: Option<Citations>,
+    #[serde(default)]
+    pub math: Option<Math>,
+}
+
+impl Entry {
+    // "2024-06-02 10:00" (UTC)
+    pub fn date(&self) -> String {
+        self.saved_at.chars().take(16).collect::<String>().replace('T', " ")
+    }
+}
+
+// Saves run in parallel in batches; the index is read and written as a whole.
+static LOCK: Mutex<()> = Mutex::new(());
+
+pub fn dir() -> Option<PathBuf> {
+    Some(dirs::data_dir()?.join("kraken"))
+}
+
+// Newest first.
+pub fn load() -> Vec<Entry> {
+    let mut entries = read();
+    entries.reverse();
+    entries
+}
+
+// In the order they were saved; empty when there is no index yet.
+fn read() -> Vec<Entry> {
+    dir()
+        .and_then(|d| std::fs::read_to_string(d.join("library.json")).ok())
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+fn write(entries: &[Entry]) -> Result<()> {
+    let dir = dir().context("no data directory on this system")?;
+    std::fs::create_dir_all(&dir)?;
+    std::fs::write(dir.join("library.json"), serde_json::to_string_pretty(entries)? + "\n")?;
+    Ok(())
+}
+
+// Records `conv` as saved at `path` with `export`; a file saved again replaces its old
+// entry.
+pub fn add(conv: &Conversation, path: &Path, export: &Export) -> Result<()> {
+    let _lock = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let path = std::path::absolute(path)?;
+    let snapshot = snapshot_path(&path).context("no data directory on this system")?;
+    if let Some(dir) = snapshot.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&snapshot, serde_json::to_string(conv)?)?;
+
+    // "chat_files/photo.png"; branch files link into the folder of the first file
+    let mut files = None;
+    conv.clone().attachments_mut(&mut |a| {
+        if let Some((folder, _)) = a.path.as_deref().and_then(|rel| rel.split_once('/')) {
+            files = path.parent().map(|dir| dir.join(folder));
+        }
+    });
+
+    let mut entries = read();
+    let watched = entries.iter().any(|e| e.path == path && e.watched);
+    entries.retain(|e| e.path != path);
+    entries.push(Entry {
+        title: conv.title.clone(),
+        source: conv.source.clone(),
+        saved_at: OffsetDateTime::now_utc().format(&Rfc3339)?,
+        path,
+        format: export.format.clone(),
+        watched,
+        files,
+        template: export.template.path.clone().filter(|_| export.format == Format::Markdown),
+        branches: Some(export.branches.clone()),
+        hidden: Some(export.hidden.clone()),
+        lang: Some(export.lang),
+        citations: Some(export.citations),
+        math: Some(export.math),
+    });
+    write(&entries)
+}
+
+pub fn set_watched(path: &Path, watched: bool) -> Result<()> {
+    let _lock = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let path = std::path::absolute(path)?;
+    let mut entries = read();
+    let entry = entries
+        .iter_mut()
+        .find(|e| e.path == path)
+        .with_context(|| format!("{} is not in the library (see `kraken library`)", path.display()))?;
+    entry.watched = watched;
+    write(&entries)
+}
+
+// The conversation as it was saved into `entry.path`.
+pub fn snapshot(entry: &Entry) -> Result<Conversation> {
+    let path = snapshot_path(&entry.path).context("no data directory on this system")?;
+    let text = std::fs::read_to_string(&path)
+        .with_context(|| format!("no saved copy of {} (it was exported by an older version)", entry.path.display()))?;
+    Ok(serde_json::from_str(&text)?)
+}
+
+// Deletes the exported file, and the attachments it was saved with, and forgets it.
+pub fn delete(entry: &Entry) -> Result<()> {
+    let _lock = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    remove_file(&entry.path)?;
+    let mut entries = read();
+    entries.retain(|e| e.path != entry.path);
+    // only the attachments Kraken wrote, and not those branch files still link to; the
+    // folder goes once nothing else is in it
+    if let Some(folder) = &entry.files
+        && let Ok(conv) = snapshot(entry)
+    {
+        let dir = entry.path.parent().unwrap_or(Path::new("."));
+        let kept: Vec<String> = entries
+            .iter()
+            .filter(|e| e.files.as_ref() == Some(folder))
+            .filter_map(|e| snapshot(e).ok())
+            .flat_map(attachment_paths)
+            .collect();
+        for rel in attachment_paths(conv).into_iter().filter(|rel| !kept.contains(rel)) {
+            let file = dir.join(rel);
+            if file.starts_with(folder) {
+                remove_file(&file)?;
+            }
+        }
+        let _ = std::fs::remove_dir(folder);
+    }
+    if let Some(snapshot) = snapshot_path(&entry.path) {
+        remove_file(&snapshot)?;
+    }
+    write(&entries)
+}
+
+// "chat_files/photo.png", relative to the exported file
+fn attachment_paths(mut conv: Conversation) -> Vec<String> {
+    let mut paths = Vec::new();
+    conv.attachments_mut(&mut |a| paths.extend(a.path.clone()));
+    paths
+}
+
+fn remove_file(path: &Path) -> Result<()> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            Err(e).with_context(|| format!("cannot delete {}", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+// Named after a hash of the exported file's path (FNV-1a, stable across builds).
+fn snapshot_path(path: &Path) -> Option<PathBuf> {
+    let hash = path
+        .to_string_lossy()
+        .bytes()
+        .fold(0xcbf29ce484222325u64, |h, b| (h ^ b as u64).wrapping_mul(0x100000001b3));
+    Some(dir()?.join("snapshots").join(format!("{:016x}.json", hash)))
+}
+
+// Opens the file in the application the system has for it.
+pub fn open(path: &Path) -> Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        std::process::Command::new("open")
+    } else if cfg!(windows) {
+        let mut c = std::process::Command::new("cmd");
+        c.args(["/C", "start", ""]);
+        c
+    } else {
+        std::process::Command::new("xdg-open")
+    };
+    command.arg(path).spawn().with_context(|| format!("cannot open {}", path.display()))?;
+    Ok(())
+}
+
+// Shows the file in the file manager: selected on macOS and Windows, its folder elsewhere.
+pub fn reveal(path: &Path) -> Result<()> {
+    let spawned = if cfg!(target_os = "macos") {
+        std::process::Command::new("open").arg("-R").arg(path).spawn()
+    } else if cfg!(windows) {
+        std::process::Command::new("explorer").arg(format!("/select,{}", path.display())).spawn()
+    } else {
+        std::process::Command::new("xdg-open").arg(path.parent().unwrap_or(Path::new("."))).spawn()
+    };
+    spawned.with_context(|| format!("cannot show {}", path.display()))?;
+    Ok(())
+}
@@ -1,42 +1,337 @@
+mod assets;
+mod batch;
+mod cli;
+mod conversation;
+mod html;
+mod http;
+mod hydration;
+mod i18n;
+mod import;
+mod json;
+mod library;
+mod math;
+mod pdf;
+mod progress;
+mod relay;
+mod settings;
+mod share;
+mod source;
+mod sync;
+mod template;
+mod watch;
+
 use anyhow::Result;
+use conversation::{human_size, Category, Conversation};
+use i18n::Lang;
+use iced::futures::channel::mpsc;
+use iced::futures::stream::{self, abortable, AbortHandle, StreamExt};
 use iced::theme::{self, Theme};
-use iced::widget::{button, column, container, pick_list, row, scrollable, text, text_input};
-use iced::{Application, Command, Element, Length, Settings};
-use regex::Regex;
+use iced::widget::{
+    button, checkbox, column, container, pick_list, progress_bar, row, scrollable, text, text_editor, text_input,
+};
+use iced::{Application, Command, Element, Length, Settings, Subscription};
+use progress::Progress;
+use relay::Relay;
 use rfd::FileDialog;
-use std::time::{SystemTime, UNIX_EPOCH};
+use source::FetchError;
+use serde::{Deserialize, Serialize};
+use template::Template;
+use std::process::ExitCode;
 use arboard::Clipboard;
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 enum Format {
     Markdown,
-    PdfDisabled,
+    Pdf,
+    Html,
+    Json,
 }
 
 impl std::fmt::Display for Format {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Format::Markdown => write!(f, "Markdown (.md)"),
-            Format::PdfDisabled => write!(f, "PDF (.pdf) — скоро"),
+            Format::Pdf => write!(f, "PDF (.pdf)"),
+            Format::Html => write!(f, "HTML (.html)"),
+            Format::Json => write!(f, "JSON (.json)"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Branches {
+    Current,
+    Inline,
+    Files,
+}
+
+impl Format {
+    fn extension(&self) -> &'static str {
+        match self {
+            Format::Markdown => "md",
+            Format::Pdf => "pdf",
+            Format::Html => "html",
+            Format::Json => "json",
+        }
+    }
+
+    fn filter_name(&self) -> &'static str {
+        match self {
+            Format::Markdown => "Markdown",
+            Format::Pdf => "PDF",
+            Format::Html => "HTML",
+            Format::Json => "JSON",
         }
     }
 }
 
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "md" | "markdown" => Ok(Format::Markdown),
+            "pdf" => Ok(Format::Pdf),
+            "html" => Ok(Format::Html),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("unknown format '{}' (expected md, pdf, html or json)", s)),
+        }
+    }
+}
+
+impl Branches {
+    fn apply(&self, conv: Conversation) -> Vec<Conversation> {
+        match self {
+            Branches::Current => vec![conv.current_thread()],
+            Branches::Inline => vec![conv],
+            Branches::Files => conv.branches(),
+        }
+    }
+}
+
+impl std::str::FromStr for Branches {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "current" => Ok(Branches::Current),
+            "inline" => Ok(Branches::Inline),
+            "files" => Ok(Branches::Files),
+            _ => Err(format!("unknown branches mode '{}' (expected current, inline or files)", s)),
+        }
+    }
+}
+
+impl Branches {
+    fn label(&self, lang: Lang) -> &'static str {
+        match self {
+            Branches::Current => lang.tr("ui.branches_current"),
+            Branches::Inline => lang.tr("ui.branches_inline"),
+            Branches::Files => lang.tr("ui.branches_files"),
+        }
+    }
+}
+
+// How Markdown marks the places an answer cites its sources.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Citations {
+    // "[^3]", with the definitions after the turn
+    Footnotes,
+    // "[[1]](https://...)"
+    Links,
+}
+
+impl Citations {
+    fn label(&self, lang: Lang) -> &'static str {
+        match self {
+            Citations::Footnotes => lang.tr("ui.citations_footnotes"),
+            Citations::Links => lang.tr("ui.citations_links"),
+        }
+    }
+}
+
+impl std::str::FromStr for Citations {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "footnotes" => Ok(Citations::Footnotes),
+            "links" => Ok(Citations::Links),
+            _ => Err(format!("unknown citation style '{}' (expected footnotes or links)", s)),
+        }
+    }
+}
+
+// What Markdown does with `\( \)` / `\[ \]` math.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Math {
+    Keep,
+    // `$...$` / `$$...$$`
+    Dollars,
+}
+
+impl Math {
+    fn label(&self, lang: Lang) -> &'static str {
+        match self {
+            Math::Keep => lang.tr("ui.math_keep"),
+            Math::Dollars => lang.tr("ui.math_dollars"),
+        }
+    }
+}
+
+impl std::str::FromStr for Math {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(Math::Keep),
+            "dollars" => Ok(Math::Dollars),
+            _ => Err(format!("unknown math mode '{}' (expected keep or dollars)", s)),
+        }
+    }
+}
+
+// A pick list entry shown under a translated label.
+#[derive(Clone, PartialEq, Eq)]
+struct Choice<T> {
+    value: T,
+    label: &'static str,
+}
+
+impl<T> std::fmt::Display for Choice<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.label)
+    }
+}
+
+// How conversations are written out; the same for the window, `fetch`, `batch` and
+// `import`.
+#[derive(Clone, Debug)]
+struct Export {
+    format: Format,
+    branches: Branches,
+    hidden: Vec<Category>,
+    // Markdown only: YAML front matter with the conversation's metadata
+    front_matter: bool,
+    // language of the labels written into the files
+    lang: Lang,
+    // Markdown layout
+    template: Template,
+    citations: Citations,
+    math: Math,
+    // file names in batches and imports, e.g. "{title}-{id}"
+    naming: String,
+}
+
+impl Export {
+    // The documents `conv` is saved as: the first at the chosen path, the rest as
+    // branch files next to it.
+    fn docs(&self, conv: Conversation) -> Vec<Conversation> {
+        self.branches.apply(conv.hide(&self.hidden))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Screen {
+    Main,
+    Settings,
+    Library,
+}
+
+// A conversation's title and the files it was saved into.
+type ImportResult = (String, std::result::Result<Vec<std::path::PathBuf>, String>);
+
 #[derive(Clone, Debug)]
 enum Message {
     UrlChanged(String),
+    RelaysChanged(String),
     FormatChanged(Format),
+    BranchesChanged(Branches),
+    CitationsChanged(Citations),
+    MathChanged(Math),
+    SectionToggled(Category, bool),
+    FrontMatterToggled(bool),
+    UiLanguageChanged(Lang),
+    OutputLanguageChanged(Lang),
+    TemplateClicked,
+    TemplateReset,
+    Show(Screen),
+    ThemeChanged(settings::Theme),
+    NamingChanged(String),
+    // a pause after the n-th edit of a text field in the settings
+    SettingsTyped(u64),
+    OutputDirClicked,
+    OutputDirReset,
     DownloadClicked,
-    Fetched(std::result::Result<String, String>),
+    CancelClicked,
+    Progress(progress::Event),
+    Fetched(std::result::Result<Conversation, String>),
     PasteClicked,
+    BatchEdited(text_editor::Action),
+    BatchLoadClicked,
+    JobsChanged(usize),
+    BatchClicked,
+    BatchDone(batch::JobResult),
+    ImportClicked,
+    Imported(std::result::Result<Vec<Conversation>, String>),
+    ImportToggled(usize, bool),
+    ImportAllToggled(bool),
+    ImportSaveClicked,
+    ImportSaved(std::result::Result<Vec<ImportResult>, String>),
+    LibraryOpen(std::path::PathBuf),
+    LibraryReveal(std::path::PathBuf),
+    LibraryExport(std::path::PathBuf, Format),
+    LibraryDelete(std::path::PathBuf),
+    LibraryUpdate(std::path::PathBuf),
+    LibraryUpdated(std::path::PathBuf, std::result::Result<usize, String>),
+    WatchToggled(std::path::PathBuf, bool),
+    WatchMinutesChanged(u64),
+    WatchTick,
+    WatchDone(std::path::PathBuf, std::result::Result<usize, String>),
 }
 
 struct App {
     url: String,
-    format: Format,
     status: String,
+    // the download in progress, to cancel it
+    download: Option<AbortHandle>,
+    // bytes of the current response received, of how many
+    received: Option<(u64, Option<u64>)>,
     preview: String,
     formats: Vec<Format>,
+    branches: Branches,
+    branch_modes: Vec<Branches>,
+    citations: Citations,
+    math: Math,
+    hidden: Vec<Category>,
+    front_matter: bool,
+    batch: text_editor::Content,
+    jobs: usize,
+    job_options: Vec<usize>,
+    batch_total: usize,
+    batch_done: usize,
+    batch_failed: usize,
+    import_path: std::path::PathBuf,
+    imported: Vec<Conversation>,
+    import_picked: Vec<bool>,
+    // the picked conversations are being written out
+    import_saving: bool,
+    // what is remembered between launches
+    settings: settings::Settings,
+    // edits of text fields in the settings so far; they are saved once typing pauses
+    settings_edits: u64,
+    screen: Screen,
+    // saved files, newest first
+    library: Vec<library::Entry>,
+    // the file whose "Delete" was clicked once and waits for confirmation
+    library_delete: Option<std::path::PathBuf>,
+    // when watched files are checked next
+    schedule: watch::Schedule,
+    template: Template,
     logs: Vec<String>,
 }
 
@@ -47,14 +342,47 @@ impl Application for App {
     type Flags = ();
 
     fn new(_flags: ()) -> (Self, Command<Self::Message>) {
+        let saved = settings::Settings::load();
+        let mut logs = Vec::new();
+        let template = match &saved.template {
+            Some(path) => Template::load(path).unwrap_or_else(|e| {
+                logs.push(saved.ui_language.trf("st.error", &[&format!("{:#}", e)]));
+                Template::default()
+            }),
+            None => Template::default(),
+        };
         (
             Self {
                 url: String::new(),
-                format: Format::Markdown,
                 status: String::new(),
+                download: None,
+                received: None,
                 preview: String::new(),
-                formats: vec![Format::Markdown, Format::PdfDisabled],
-                logs: Vec::new(),
+                formats: vec![Format::Markdown, Format::Pdf, Format::Html, Format::Json],
+                branches: Branches::Current,
+                branch_modes: vec![Branches::Current, Branches::Inline, Branches::Files],
+                citations: Citations::Footnotes,
+                math: Math::Keep,
+                hidden: Vec::new(),
+                front_matter: false,
+                batch: text_editor::Content::new(),
+                jobs: batch::DEFAULT_JOBS,
+                job_options: vec![1, 2, 4, 8, 16],
+                batch_total: 0,
+                batch_done: 0,
+                batch_failed: 0,
+                import_path: std::path::PathBuf::new(),
+                imported: Vec::new(),
+                import_picked: Vec::new(),
+                import_saving: false,
+                schedule: watch::Schedule::new(saved.watch_minutes),
+                settings: saved,
+                settings_edits: 0,
+                screen: Screen::Main,
+                library: library::load(),
+                library_delete: None,
+                template,
+                logs,
             },
             Command::none(),
         )
@@ -65,100 +393,686 @@ impl Application for App {
     }
 
     fn theme(&self) -> Theme {
-        theme::Theme::Dark
+        match self.settings.theme {
+            settings::Theme::Dark => theme::Theme::Dark,
+            settings::Theme::Light => theme::Theme::Light,
+        }
     }
 
-    // no subscriptions needed
+    // Watched files are looked at every half minute; `watch::Schedule` decides which
+    // are due.
+    fn subscription(&self) -> Subscription<Message> {
+        if self.library.iter().any(|e| e.watched) {
+            iced::time::every(std::time::Duration::from_secs(30)).map(|_| Message::WatchTick)
+        } else {
+            Subscription::none()
+        }
+    }
 
     fn update(&mut self, message: Self::Message) -> Command<Self::Message> {
         match message {
             Message::UrlChanged(s) => {
                 self.url = s;
             }
+            Message::RelaysChanged(s) => {
+                self.settings.relays = s;
+                return self.save_settings_later();
+            }
             Message::FormatChanged(fmt) => {
-                self.format = fmt;
+                self.settings.format = fmt;
+                self.save_settings();
+            }
+            Message::BranchesChanged(mode) => {
+                self.branches = mode;
+            }
+            Message::CitationsChanged(style) => {
+                self.citations = style;
+            }
+            Message::MathChanged(math) => {
+                self.math = math;
+            }
+            Message::SectionToggled(category, shown) => {
+                self.hidden.retain(|c| *c != category);
+                if !shown {
+                    self.hidden.push(category);
+                }
+            }
+            Message::FrontMatterToggled(on) => {
+                self.front_matter = on;
+            }
+            Message::Show(screen) => {
+                if screen == Screen::Library {
+                    self.library = library::load();
+                    self.library_delete = None;
+                }
+                self.screen = screen;
+            }
+            Message::ThemeChanged(theme) => {
+                self.settings.theme = theme;
+                self.save_settings();
+            }
+            Message::NamingChanged(naming) => {
+                self.settings.naming = naming;
+                return self.save_settings_later();
+            }
+            Message::SettingsTyped(edit) => {
+                if edit == self.settings_edits {
+                    self.save_settings();
+                }
+            }
+            Message::OutputDirClicked => {
+                if let Some(dir) = self.dialog().pick_folder() {
+                    self.settings.output_dir = Some(dir);
+                    self.save_settings();
+                }
+            }
+            Message::OutputDirReset => {
+                self.settings.output_dir = None;
+                self.save_settings();
+            }
+            Message::UiLanguageChanged(lang) => {
+                self.settings.ui_language = lang;
+                self.save_settings();
+            }
+            Message::OutputLanguageChanged(lang) => {
+                self.settings.output_language = lang;
+                self.save_settings();
+            }
+            Message::TemplateClicked => {
+                let Some(path) = FileDialog::new().add_filter("Template", &["md", "txt", "tmpl"]).pick_file() else {
+                    return Command::none();
+                };
+                match Template::load(&path) {
+                    Ok(template) => {
+                        self.push_log(&self.settings.ui_language.trf("log.template", &[&path.display()]));
+                        self.template = template;
+                        self.settings.template = Some(path);
+                        self.save_settings();
+                    }
+                    Err(e) => {
+                        self.status = self.settings.ui_language.trf("st.error", &[&format!("{:#}", e)]);
+                        self.push_log(&self.status.clone());
+                    }
+                }
+            }
+            Message::TemplateReset => {
+                self.template = Template::default();
+                self.settings.template = None;
+                self.save_settings();
             }
             Message::PasteClicked => {
                 if let Some(txt) = read_clipboard_text() {
                     self.url = txt;
-                    self.push_log("Pasted URL from clipboard");
+                    self.push_log(self.settings.ui_language.tr("log.pasted"));
+                }
+            }
+            Message::BatchEdited(action) => {
+                self.batch.perform(action);
+            }
+            Message::BatchLoadClicked => {
+                if let Some(path) = FileDialog::new().add_filter("Text", &["txt"]).pick_file() {
+                    match std::fs::read_to_string(&path) {
+                        Ok(list) => {
+                            self.batch = text_editor::Content::with_text(&list);
+                            self.push_log(&self.settings.ui_language.trf("log.loaded_list", &[&path.display()]));
+                        }
+                        Err(e) => self.push_log(&self.settings.ui_language.trf("st.error", &[&e])),
+                    }
+                }
+            }
+            Message::JobsChanged(n) => {
+                self.jobs = n;
+            }
+            Message::BatchClicked => {
+                if self.batch_done < self.batch_total {
+                    return Command::none();
+                }
+                let relays = match relay::parse_chain(&self.settings.relays) {
+                    Ok(r) => r,
+                    Err(e) => {
+                        self.status = self.settings.ui_language.trf("st.error", &[&e]);
+                        return Command::none();
+                    }
+                };
+                let urls = batch::parse_urls(&self.batch.text());
+                if urls.is_empty() {
+                    self.status = self.settings.ui_language.tr("st.batch_empty").into();
+                    return Command::none();
                 }
+                let Some(dir) = self.dialog().pick_folder() else {
+                    self.status = self.settings.ui_language.tr("st.batch_cancelled").into();
+                    return Command::none();
+                };
+                self.remember_dir(&dir);
+                self.batch_total = urls.len();
+                self.batch_done = 0;
+                self.batch_failed = 0;
+                self.status = self.settings.ui_language.trf("st.batch_progress", &[&0, &self.batch_total]);
+                self.push_log(&self.settings.ui_language.trf("log.start_batch", &[&urls.len(), &dir.display()]));
+                let opts = batch::JobOptions { dir, export: self.export(), relays, lang: self.settings.ui_language };
+                let permits = std::sync::Arc::new(tokio::sync::Semaphore::new(self.jobs));
+                return Command::batch(urls.into_iter().map(|url| {
+                    Command::perform(batch::job(url, opts.clone(), permits.clone()), Message::BatchDone)
+                }));
+            }
+            Message::BatchDone(done) => {
+                self.batch_done += 1;
+                match done.result {
+                    Ok(paths) => {
+                        for path in paths {
+                            self.push_log(&self.settings.ui_language.trf("log.saved_item", &[&done.url, &path.display()]));
+                        }
+                    }
+                    Err(e) => {
+                        self.batch_failed += 1;
+                        self.push_log(&self.settings.ui_language.trf("log.failed_item", &[&done.url, &e]));
+                    }
+                }
+                self.status = self.settings.ui_language.trf("st.batch_progress", &[&self.batch_done, &self.batch_total]);
+                if self.batch_failed > 0 {
+                    self.status.push_str(&self.settings.ui_language.trf("st.batch_failed", &[&self.batch_failed]));
+                }
+            }
+            Message::ImportClicked => {
+                let Some(path) = FileDialog::new()
+                    .add_filter("ChatGPT data export", &["zip", "json"])
+                    .pick_file()
+                else {
+                    return Command::none();
+                };
+                self.status = self.settings.ui_language.tr("st.reading_export").into();
+                self.push_log(&self.settings.ui_language.trf("log.import", &[&path.display()]));
+                self.import_path = path.clone();
+                return Command::perform(
+                    async move {
+                        tokio::task::spawn_blocking(move || import::read(&path))
+                            .await
+                            .map_err(|e| e.to_string())?
+                            .map_err(|e| format!("{:#}", e))
+                    },
+                    Message::Imported,
+                );
+            }
+            Message::Imported(res) => match res {
+                Ok(convs) => {
+                    self.status = self.settings.ui_language.trf("st.export_count", &[&convs.len()]);
+                    self.push_log(&self.settings.ui_language.trf("log.export_count", &[&convs.len()]));
+                    self.import_picked = vec![false; convs.len()];
+                    self.imported = convs;
+                }
+                Err(e) => {
+                    self.status = self.settings.ui_language.trf("st.error", &[&e]);
+                    self.push_log(&self.settings.ui_language.trf("st.error", &[&e]));
+                }
+            },
+            Message::ImportToggled(i, on) => {
+                if let Some(picked) = self.import_picked.get_mut(i) {
+                    *picked = on;
+                }
+            }
+            Message::ImportAllToggled(on) => {
+                self.import_picked.iter_mut().for_each(|p| *p = on);
+            }
+            Message::ImportSaveClicked => {
+                let convs: Vec<Conversation> = self
+                    .imported
+                    .iter()
+                    .zip(&self.import_picked)
+                    .filter(|(_, picked)| **picked)
+                    .map(|(conv, _)| conv.clone())
+                    .collect();
+                if self.import_saving {
+                    return Command::none();
+                }
+                if convs.is_empty() {
+                    self.status = self.settings.ui_language.tr("st.pick_one").into();
+                    return Command::none();
+                }
+                let Some(dir) = self.dialog().pick_folder() else {
+                    self.status = self.settings.ui_language.tr("st.import_cancelled").into();
+                    return Command::none();
+                };
+                self.remember_dir(&dir);
+                self.import_saving = true;
+                self.status = self.settings.ui_language.trf("st.saving_import", &[&convs.len()]);
+                let (archive, export) = (self.import_path.clone(), self.export());
+                return Command::perform(
+                    async move {
+                        tokio::task::spawn_blocking(move || {
+                            import::save_all(&archive, &convs, &dir, &export)
+                                .into_iter()
+                                .map(|(title, result)| (title, result.map_err(|e| format!("{:#}", e))))
+                                .collect()
+                        })
+                        .await
+                        .map_err(|e| e.to_string())
+                    },
+                    Message::ImportSaved,
+                );
+            }
+            Message::ImportSaved(results) => {
+                self.import_saving = false;
+                let results = match results {
+                    Ok(results) => results,
+                    Err(e) => {
+                        self.fail(&e);
+                        return Command::none();
+                    }
+                };
+                let mut failed = 0;
+                for (title, result) in &results {
+                    match result {
+                        Ok(paths) => {
+                            for path in paths {
+                                self.push_log(&self.settings.ui_language.trf("log.saved_item", &[title, &path.display()]));
+                            }
+                        }
+                        Err(e) => {
+                            failed += 1;
+                            self.push_log(&self.settings.ui_language.trf("log.failed_item", &[title, e]));
+                        }
+                    }
+                }
+                self.status = self.settings.ui_language.trf("st.imported", &[&(results.len() - failed), &results.len()]);
+            }
+            Message::LibraryOpen(path) => {
+                if let Err(e) = library::open(&path) {
+                    self.fail(&format!("{:#}", e));
+                }
+            }
+            Message::LibraryReveal(path) => {
+                if let Err(e) = library::reveal(&path) {
+                    self.fail(&format!("{:#}", e));
+                }
+            }
+            Message::LibraryExport(path, format) => {
+                let Some(entry) = self.library.iter().find(|e| e.path == path) else {
+                    return Command::none();
+                };
+                // the copy is what went into the file: branches and hidden sections
+                // were applied already
+                let conv = match library::snapshot(entry) {
+                    Ok(conv) => conv,
+                    Err(e) => {
+                        self.fail(&format!("{:#}", e));
+                        return Command::none();
+                    }
+                };
+                let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+                let mut dialog = FileDialog::new()
+                    .add_filter(format.filter_name(), &[format.extension()])
+                    .set_file_name(format!("{}.{}", stem, format.extension()));
+                if let Some(dir) = path.parent() {
+                    dialog = dialog.set_directory(dir);
+                }
+                let Some(target) = dialog.save_file() else {
+                    self.status = self.settings.ui_language.tr("st.save_cancelled").into();
+                    return Command::none();
+                };
+                let export = Export { format, ..self.export() };
+                match save(&export, &[conv], &target) {
+                    Ok(saved) => {
+                        self.status = self.settings.ui_language.tr("st.saved").into();
+                        for target in saved {
+                            self.push_log(&self.settings.ui_language.trf("log.file_saved", &[&target.display()]));
+                        }
+                    }
+                    Err(e) => self.fail(&format!("{:#}", e)),
+                }
+                self.library = library::load();
+            }
+            Message::LibraryUpdate(path) => {
+                if self.schedule.running(&path) {
+                    return Command::none();
+                }
+                let Some(entry) = self.library.iter().find(|e| e.path == path).cloned() else {
+                    return Command::none();
+                };
+                let relays = match relay::parse_chain(&self.settings.relays) {
+                    Ok(r) => r,
+                    Err(e) => {
+                        self.fail(&e);
+                        return Command::none();
+                    }
+                };
+                self.schedule.start(&path);
+                self.status = self.settings.ui_language.trf("st.updating", &[&entry.title]);
+                self.push_log(&self.settings.ui_language.trf("log.start_update", &[&path.display(), &entry.source]));
+                let (export, lang) = (self.export(), self.settings.ui_language);
+                return Command::perform(
+                    async move { sync::update(entry, export, relays).await.map_err(|e| source::error_text(&e, lang)) },
+                    move |res| Message::LibraryUpdated(path.clone(), res),
+                );
+            }
+            Message::LibraryUpdated(path, res) => {
+                // for a watched file this was a check like any other
+                if self.library.iter().any(|e| e.path == path && e.watched) {
+                    self.schedule.done(&path, res.is_ok());
+                } else {
+                    self.schedule.stop(&path);
+                }
+                match res {
+                    Ok(0) => {
+                        self.status = self.settings.ui_language.tr("st.up_to_date").into();
+                        self.push_log(&self.settings.ui_language.trf("log.up_to_date", &[&path.display()]));
+                    }
+                    Ok(added) => {
+                        self.status = self.settings.ui_language.trf("st.updated", &[&added]);
+                        self.push_log(&self.settings.ui_language.trf("log.updated", &[&path.display(), &added]));
+                    }
+                    Err(e) => self.fail(&e),
+                }
+                self.library = library::load();
+            }
+            Message::WatchToggled(path, on) => {
+                match library::set_watched(&path, on) {
+                    Ok(()) => {
+                        let key = if on { "log.watching" } else { "log.unwatched" };
+                        self.push_log(&self.settings.ui_language.trf(key, &[&path.display()]));
+                    }
+                    Err(e) => self.fail(&format!("{:#}", e)),
+                }
+                self.library = library::load();
+            }
+            Message::WatchMinutesChanged(minutes) => {
+                self.settings.watch_minutes = minutes;
+                self.schedule.set_interval(minutes);
+                self.save_settings();
+            }
+            Message::WatchTick => {
+                let Ok(relays) = relay::parse_chain(&self.settings.relays) else {
+                    return Command::none();
+                };
+                // files being updated by hand are `running` too, and skipped until they're done
+                let due: Vec<library::Entry> =
+                    self.library.iter().filter(|e| e.watched && self.schedule.due(&e.path)).cloned().collect();
+                return Command::batch(due.into_iter().map(|entry| {
+                    self.schedule.start(&entry.path);
+                    let path = entry.path.clone();
+                    let (export, relays, lang) = (self.export(), relays.clone(), self.settings.ui_language);
+                    Command::perform(
+                        async move { sync::update(entry, export, relays).await.map_err(|e| source::error_text(&e, lang)) },
+                        move |res| Message::WatchDone(path.clone(), res),
+                    )
+                }));
+            }
+            Message::WatchDone(path, res) => {
+                let wait = self.schedule.done(&path, res.is_ok());
+                match res {
+                    Ok(0) => {}
+                    Ok(added) => {
+                        self.push_log(&self.settings.ui_language.trf("log.watch_updated", &[&added, &path.display()]));
+                        self.library = library::load();
+                    }
+                    Err(e) => {
+                        let minutes = wait.as_secs() / 60;
+                        self.push_log(&self.settings.ui_language.trf("log.watch_failed", &[&path.display(), &minutes, &e]));
+                    }
+                }
+            }
+            Message::LibraryDelete(path) => {
+                if self.library_delete.as_ref() != Some(&path) {
+                    self.library_delete = Some(path);
+                    return Command::none();
+                }
+                self.library_delete = None;
+                let Some(entry) = self.library.iter().find(|e| e.path == path) else {
+                    return Command::none();
+                };
+                match library::delete(entry) {
+                    Ok(()) => self.push_log(&self.settings.ui_language.trf("log.deleted", &[&path.display()])),
+                    Err(e) => self.fail(&format!("{:#}", e)),
+                }
+                self.library = library::load();
             }
             Message::DownloadClicked => {
-                self.status = "Downloading...".into();
+                if self.download.is_some() {
+                    return Command::none();
+                }
+                if self.url.trim().is_empty() {
+                    self.status = self.settings.ui_language.tr("st.enter_link").into();
+                    return Command::none();
+                }
+                let relays = match relay::parse_chain(&self.settings.relays) {
+                    Ok(r) => r,
+                    Err(e) => {
+                        self.status = self.settings.ui_language.trf("st.error", &[&e]);
+                        return Command::none();
+                    }
+                };
+                self.status = self.settings.ui_language.tr("st.downloading").into();
                 self.preview.clear();
                 let url = self.url.clone();
-                self.push_log(&format!("Start download: {}", url));
-                return Command::perform(async move {
-                    fetch_and_convert(url).await.map_err(|e| e.to_string())
-                }, Message::Fetched);
-            }
-            Message::Fetched(res) => match res {
-                Ok(md) => {
-                    self.preview = md.clone();
-                    self.status = "Ready. Choose where to save.".into();
-                    self.push_log("Fetched & parsed successfully");
-
-                    if let Format::Markdown = self.format {
-                        if let Some(path) = FileDialog::new()
-                            .add_filter("Markdown", &["md"]) 
-                            .set_file_name("chatgpt_conversation.md")
-                            .save_file()
-                        {
-                            let _ = std::fs::write(path, md);
-                            self.status = "Saved".into();
-                            self.push_log("File saved");
+                let assets = self.settings.format == Format::Markdown;
+                self.push_log(&self.settings.ui_language.trf("log.start_download", &[&url, &relay::chain_to_string(&relays)]));
+                let (sender, events) = mpsc::unbounded();
+                let progress = Progress::new(move |event| {
+                    let _ = sender.unbounded_send(event);
+                });
+                let lang = self.settings.ui_language;
+                let fetch = async move {
+                    let conv = fetch_and_convert(url, relays, assets, progress).await;
+                    Message::Fetched(conv.map_err(|e| source::error_text(&e, lang)))
+                };
+                // the events as they come, then the result
+                let (task, handle) = abortable(stream::select(events.map(Message::Progress), stream::once(fetch)));
+                self.download = Some(handle);
+                self.received = None;
+                return Command::run(task, |message| message);
+            }
+            Message::CancelClicked => {
+                if let Some(download) = self.download.take() {
+                    download.abort();
+                    self.status = self.settings.ui_language.tr("st.cancelled").into();
+                    self.push_log(self.settings.ui_language.tr("st.cancelled"));
+                }
+            }
+            Message::Progress(event) => {
+                // stragglers after the result or a cancel
+                if self.download.is_none() {
+                    return Command::none();
+                }
+                let lang = self.settings.ui_language;
+                match event {
+                    progress::Event::Attempt(stage, relay) => {
+                        self.status = lang.trf("st.attempt", &[&stage.label(lang), &relay]);
+                        self.received = None;
+                    }
+                    progress::Event::Succeeded(stage, relay) => {
+                        self.push_log(&lang.trf("log.attempt_ok", &[&stage.label(lang), &relay]));
+                    }
+                    progress::Event::Failed(stage, relay, error) => {
+                        self.push_log(&lang.trf("log.attempt_failed", &[&stage.label(lang), &relay, &error]));
+                    }
+                    progress::Event::Bytes(received, total) => self.received = Some((received, total)),
+                }
+            }
+            Message::Fetched(res) => {
+                self.download = None;
+                match res {
+                    Ok(conv) => {
+                        if self.branches == Branches::Current && conv.has_branches() {
+                            self.push_log(self.settings.ui_language.tr("log.branches_dropped"));
+                        }
+                        let export = self.export();
+                        let docs = export.docs(conv);
+                        self.preview = export.template.render(&docs[0], &export);
+                        self.status = self.settings.ui_language.tr("st.ready").into();
+                        self.push_log(&match &docs[0].relay {
+                            Some(r) => self.settings.ui_language.trf("log.fetched_via", &[r]),
+                            None => self.settings.ui_language.tr("log.fetched_direct").to_string(),
+                        });
+
+                        let format = &self.settings.format;
+                        let dialog = self
+                            .dialog()
+                            .add_filter(format.filter_name(), &[format.extension()])
+                            .set_file_name(batch::file_name(&docs[0], format, &self.settings.naming));
+                        if let Some(path) = dialog.save_file() {
+                            if let Some(dir) = path.parent() {
+                                self.remember_dir(dir);
+                            }
+                            match save(&export, &docs, &path) {
+                                Ok(saved) => {
+                                    self.status = self.settings.ui_language.tr("st.saved").into();
+                                    for target in saved {
+                                        self.push_log(&self.settings.ui_language.trf("log.file_saved", &[&target.display()]));
+                                    }
+                                }
+                                Err(e) => {
+                                    self.status = self.settings.ui_language.trf("st.error", &[&e]);
+                                    self.push_log(&self.settings.ui_language.trf("st.error", &[&e]));
+                                }
+                            }
                         } else {
-                            self.status = "Save cancelled".into();
-                            self.push_log("Save cancelled");
+                            self.status = self.settings.ui_language.tr("st.save_cancelled").into();
+                            self.push_log(self.settings.ui_language.tr("st.save_cancelled"));
                         }
-                    } else {
-                        self.status = "PDF is disabled for now".into();
-                        self.push_log("PDF is disabled");
+                    }
+                    Err(e) => {
+                        self.status = self.settings.ui_language.trf("st.error", &[&e]);
+                        self.push_log(&self.settings.ui_language.trf("st.error", &[&e]));
                     }
                 }
-                Err(e) => {
-                    self.status = format!("Error: {}", e);
-                    self.push_log(&format!("Error: {}", e));
-                }
-            },
+            }
         }
         Command::none()
     }
 
     fn view(&self) -> Element<'_, Self::Message> {
+        match self.screen {
+            Screen::Settings => return self.settings_view(),
+            Screen::Library => return self.library_view(),
+            Screen::Main => {}
+        }
+        let tr = |key| self.settings.ui_language.tr(key);
         let url_input = text_input("https://chatgpt.com/share/...", &self.url)
             .on_input(Message::UrlChanged)
-            .on_paste(|s| Message::UrlChanged(s))
+            .on_paste(Message::UrlChanged)
             .width(Length::Fill);
 
-        let fmt_combo = pick_list(self.formats.clone(), Some(self.format.clone()), Message::FormatChanged);
+        let fmt_combo = pick_list(self.formats.clone(), Some(self.settings.format.clone()), Message::FormatChanged);
+        let choice = |mode: &Branches| Choice { value: mode.clone(), label: mode.label(self.settings.ui_language) };
+        let branches_combo = pick_list(
+            self.branch_modes.iter().map(choice).collect::<Vec<_>>(),
+            Some(choice(&self.branches)),
+            |c| Message::BranchesChanged(c.value),
+        );
+        let style = |citations: Citations| Choice { value: citations, label: citations.label(self.settings.ui_language) };
+        let citations_combo = pick_list(
+            vec![style(Citations::Footnotes), style(Citations::Links)],
+            Some(style(self.citations)),
+            |c| Message::CitationsChanged(c.value),
+        );
+        let delimiters = |math: Math| Choice { value: math, label: math.label(self.settings.ui_language) };
+        let math_combo = pick_list(
+            vec![delimiters(Math::Keep), delimiters(Math::Dollars)],
+            Some(delimiters(self.math)),
+            |c| Message::MathChanged(c.value),
+        );
 
-        let paste_btn = button(text("Paste")).on_press(Message::PasteClicked);
-        let download_btn = button(text("Download")).on_press(Message::DownloadClicked);
+        let paste_btn = button(text(tr("ui.paste"))).on_press(Message::PasteClicked);
+        let download_btn =
+            button(text(tr("ui.download"))).on_press_maybe(self.download.is_none().then_some(Message::DownloadClicked));
+        let cancel_btn =
+            button(text(tr("ui.cancel"))).on_press_maybe(self.download.is_some().then_some(Message::CancelClicked));
 
         let top = row![
-            text("Public link:").width(Length::Shrink),
+            text(tr("ui.public_link")).width(Length::Shrink),
             url_input,
             paste_btn,
+            button(text(tr("ui.library"))).on_press(Message::Show(Screen::Library)),
+            button(text(tr("ui.settings"))).on_press(Message::Show(Screen::Settings)),
         ]
         .spacing(8);
 
         let second = row![
-            text("Format:").width(Length::Shrink),
+            text(tr("ui.format")).width(Length::Shrink),
             fmt_combo,
+            text(tr("ui.branches")).width(Length::Shrink),
+            branches_combo,
             download_btn,
+            cancel_btn,
             text(&self.status)
         ]
         .spacing(12)
         .align_items(iced::Alignment::Center);
 
+        let section = |label: &str, category: Category| {
+            checkbox(label, !self.hidden.contains(&category))
+                .on_toggle(move |shown| Message::SectionToggled(category, shown))
+        };
+        let sections_row = row![
+            text(tr("ui.include")).width(Length::Shrink),
+            section(tr("ui.code"), Category::Code),
+            section(tr("ui.browsing"), Category::Browsing),
+            section(tr("ui.tools"), Category::Tools),
+            section(tr("ui.system"), Category::System),
+            checkbox(tr("ui.front_matter"), self.front_matter).on_toggle(Message::FrontMatterToggled),
+            text(tr("ui.citations")).width(Length::Shrink),
+            citations_combo,
+            text(tr("ui.math")).width(Length::Shrink),
+            math_combo,
+        ]
+        .spacing(12)
+        .align_items(iced::Alignment::Center);
+
+        let batch_editor = text_editor(&self.batch).on_action(Message::BatchEdited).height(Length::Fixed(90.0));
+        let jobs_combo = pick_list(self.job_options.clone(), Some(self.jobs), Message::JobsChanged);
+        let batch_running = self.batch_done < self.batch_total;
+        let batch_btn = button(text(tr("ui.download_all")))
+            .on_press_maybe((!batch_running).then_some(Message::BatchClicked));
+        let batch_row = row![
+            text(tr("ui.batch")).width(Length::Shrink),
+            button(text(tr("ui.load_file"))).on_press(Message::BatchLoadClicked),
+            text(tr("ui.parallel")).width(Length::Shrink),
+            jobs_combo,
+            batch_btn,
+        ]
+        .spacing(12)
+        .align_items(iced::Alignment::Center);
+
+        let all_picked = !self.import_picked.is_empty() && self.import_picked.iter().all(|p| *p);
+        let import_row = row![
+            text(tr("ui.account_export")).width(Length::Shrink),
+            button(text(tr("ui.open_export"))).on_press(Message::ImportClicked),
+            checkbox(tr("ui.all"), all_picked).on_toggle_maybe((!self.imported.is_empty()).then_some(Message::ImportAllToggled)),
+            button(text(tr("ui.save_selected"))).on_press_maybe(
+                (!self.import_saving && self.import_picked.contains(&true)).then_some(Message::ImportSaveClicked)
+            ),
+        ]
+        .spacing(12)
+        .align_items(iced::Alignment::Center);
+        let import_list = column(self.imported.iter().zip(&self.import_picked).enumerate().map(|(i, (conv, picked))| {
+            let label = format!("{}  {}", import::date(conv), conv.title);
+            checkbox(label, *picked).on_toggle(move |on| Message::ImportToggled(i, on)).into()
+        }))
+        .spacing(4);
+
         let preview = scrollable(container(text(&self.preview)).padding(8)).height(Length::Fill);
 
-        let logs_joined = if self.logs.is_empty() { String::from("(log is empty)") } else { self.logs.join("\n") };
+        let logs_joined = if self.logs.is_empty() { String::from(tr("ui.log_empty")) } else { self.logs.join("\n") };
         let log_panel = scrollable(container(text(logs_joined)).padding(8)).height(Length::Fixed(120.0));
 
-        container(column![top, second, preview, text("Log:"), log_panel].spacing(12).padding(12))
+        let mut content = column![top, second].spacing(12).padding(12);
+        if let (Some(_), Some((received, total))) = (&self.download, self.received) {
+            let lang = self.settings.ui_language;
+            let mut bar = row![].spacing(12).align_items(iced::Alignment::Center);
+            let size = match total {
+                Some(total) => {
+                    let done = received as f32 / total.max(1) as f32;
+                    bar = bar.push(progress_bar(0.0..=1.0, done).width(Length::Fixed(300.0)));
+                    format!("{} / {}", human_size(received, lang), human_size(total, lang))
+                }
+                None => human_size(received, lang),
+            };
+            content = content.push(bar.push(text(size)));
+        }
+        let mut content = content.push(sections_row).push(batch_row).push(batch_editor).push(import_row);
+        if !self.imported.is_empty() {
+            content = content.push(scrollable(import_list).height(Length::Fixed(140.0)));
+        }
+        container(content.push(preview).push(text(tr("ui.log"))).push(log_panel))
             .width(Length::Fill)
             .height(Length::Fill)
             .center_x()
@@ -166,148 +1080,232 @@ impl Application for App {
     }
 }
 
-impl App {}
+impl App {
+    fn settings_view(&self) -> Element<'_, Message> {
+        let lang = self.settings.ui_language;
+        let tr = |key| lang.tr(key);
+        let label = |key| text(tr(key)).width(Length::Fixed(180.0));
+        let line = |items: Vec<Element<'static, Message>>| row(items).spacing(12).align_items(iced::Alignment::Center);
 
-#[tokio::main]
-async fn main() -> iced::Result {
-    App::run(Settings::default())
-}
+        let theme = |theme: settings::Theme| Choice { value: theme, label: theme.label(lang) };
+        let folder = match &self.settings.output_dir {
+            Some(dir) => dir.display().to_string(),
+            None => tr("ui.output_dir_none").to_string(),
+        };
+        let template_name = match &self.settings.template {
+            Some(path) => path.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+            None => tr("ui.template_default").to_string(),
+        };
+        let file = settings::path().map(|p| p.display().to_string()).unwrap_or_default();
 
-async fn fetch_and_convert(share_url: String) -> Result<String> {
-    if share_url.trim().is_empty() {
-        anyhow::bail!("Укажите ссылку");
-    }
-
-    let normalized = share_url
-        .trim()
-        .trim_start_matches("https://")
-        .trim_start_matches("http://")
-        .to_string();
-
-    if let Some(md) = try_fetch_backend_json(&normalized).await? {
-        return Ok(md);
-    }
-
-    // Fallback: r.jina.ai -> Markdown страницы
-    let cache_buster = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_secs();
-    let sep = if normalized.contains('?') { '&' } else { '?' };
-    let url = format!(
-        "https://r.jina.ai/http://{}{}{}_ts={}",
-        normalized, sep, if sep == '&' { "" } else { "" }, cache_buster
-    );
-
-    let client = reqwest::Client::builder()
-        .user_agent("Mozilla/5.0")
-        .build()?;
-    let text = client
-        .get(&url)
-        .header("Cache-Control", "no-cache")
-        .header("Pragma", "no-cache")
-        .send()
-        .await?
-        .error_for_status()?
-        .text()
-        .await?;
-
-    let title = Regex::new(r"^Title:\s*(.*)$")
-        .unwrap()
-        .captures(&text)
-        .and_then(|c| c.get(1))
-        .map(|m| m.as_str().to_string())
-        .unwrap_or_else(|| "ChatGPT Conversation".to_string());
-
-    let mut out = String::new();
-    out.push_str(&format!("# {}\n\n", title));
-    out.push_str(&format!("**Источник**: {}\n\n", share_url));
-
-    if let Some(m) = Regex::new(r"(?m)^##### You said:")
-        .unwrap()
-        .find(&text)
-    {
-        out.push_str(&text[m.start()..]);
-    } else {
-        out.push_str(&text);
-    }
-
-    Ok(out)
-}
+        let content = column![
+            text(tr("ui.settings")).size(20),
+            line(vec![
+                label("ui.ui_language").into(),
+                pick_list(Lang::ALL, Some(lang), Message::UiLanguageChanged).into(),
+            ]),
+            line(vec![
+                label("ui.output_language").into(),
+                pick_list(Lang::ALL, Some(self.settings.output_language), Message::OutputLanguageChanged).into(),
+            ]),
+            line(vec![
+                label("ui.theme").into(),
+                pick_list(
+                    settings::Theme::ALL.map(theme).to_vec(),
+                    Some(theme(self.settings.theme)),
+                    |c| Message::ThemeChanged(c.value),
+                )
+                .into(),
+            ]),
+            line(vec![
+                label("ui.format").into(),
+                pick_list(self.formats.clone(), Some(self.settings.format.clone()), Message::FormatChanged).into(),
+            ]),
+            line(vec![
+                label("ui.relays").into(),
+                text_input("direct, jina, https://relay.example/{url}", &self.settings.relays)
+                    .on_input(Message::RelaysChanged)
+                    .width(Length::Fill)
+                    .into(),
+            ]),
+            line(vec![
+                label("ui.output_dir").into(),
+                text(folder).into(),
+                button(text(tr("ui.choose"))).on_press(Message::OutputDirClicked).into(),
+                button(text(tr("ui.reset")))
+                    .on_press_maybe(self.settings.output_dir.is_some().then_some(Message::OutputDirReset))
+                    .into(),
+            ]),
+            line(vec![
+                label("ui.naming").into(),
+                text_input(batch::DEFAULT_NAMING, &self.settings.naming)
+                    .on_input(Message::NamingChanged)
+                    .width(Length::Fixed(260.0))
+                    .into(),
+                text(tr("ui.naming_hint")).into(),
+            ]),
+            line(vec![
+                label("ui.template").into(),
+                text(template_name).into(),
+                button(text(tr("ui.choose"))).on_press(Message::TemplateClicked).into(),
+                button(text(tr("ui.template_default")))
+                    .on_press_maybe(self.settings.template.is_some().then_some(Message::TemplateReset))
+                    .into(),
+            ]),
+            line(vec![
+                label("ui.watch_minutes").into(),
+                pick_list(vec![5, 10, 15, 30, 60, 120], Some(self.settings.watch_minutes), Message::WatchMinutesChanged)
+                    .into(),
+            ]),
+            text(lang.trf("ui.settings_file", &[&file])).size(12),
+            button(text(tr("ui.back"))).on_press(Message::Show(Screen::Main)),
+        ]
+        .spacing(12)
+        .padding(12);
+        container(content).width(Length::Fill).height(Length::Fill).into()
+    }
 
-async fn try_fetch_backend_json(normalized_share: &str) -> Result<Option<String>> {
-    let id = extract_share_id(normalized_share).unwrap_or_else(|| normalized_share.to_string());
-    let ts = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
-    let candidates = vec![
-        format!("https://r.jina.ai/http://chatgpt.com/backend-api/share/{}?_ts={}", id, ts),
-        format!("https://r.jina.ai/https://chatgpt.com/backend-api/share/{}?_ts={}", id, ts),
-    ];
-
-    let client = reqwest::Client::builder().user_agent("Mozilla/5.0").build()?;
-
-    for u in candidates {
-        let resp = client
-            .get(&u)
-            .header("Cache-Control", "no-cache")
-            .header("Pragma", "no-cache")
-            .send()
-            .await?;
-        if !resp.status().is_success() {
-            continue;
-        }
-        let body = resp.text().await?;
-        if let Some(md) = parse_backend_to_markdown(&body, normalized_share) {
-            return Ok(Some(md));
-        }
+    fn library_view(&self) -> Element<'_, Message> {
+        let lang = self.settings.ui_language;
+        let tr = |key| lang.tr(key);
+        let entries = column(self.library.iter().map(|entry| {
+            let path = entry.path.clone();
+            let mut details = format!("{}  ·  {}  ·  {}", entry.date(), entry.format.filter_name(), path.display());
+            if !path.exists() {
+                details = format!("{}  {}", details, tr("ui.missing"));
+            }
+            let others: Vec<Format> = self.formats.iter().filter(|f| **f != entry.format).cloned().collect();
+            let export_path = path.clone();
+            let delete = if self.library_delete.as_ref() == Some(&path) { "ui.confirm_delete" } else { "ui.delete" };
+            column![
+                text(&entry.title).size(16),
+                text(details).size(12),
+                text(&entry.source).size(12),
+                row![
+                    button(text(tr("ui.open"))).on_press(Message::LibraryOpen(path.clone())),
+                    button(text(tr("ui.reveal"))).on_press(Message::LibraryReveal(path.clone())),
+                    button(text(tr("ui.update")))
+                        .on_press_maybe((!self.schedule.running(&path)).then(|| Message::LibraryUpdate(path.clone()))),
+                    checkbox(tr("ui.watch"), entry.watched).on_toggle({
+                        let path = path.clone();
+                        move |on| Message::WatchToggled(path.clone(), on)
+                    }),
+                    pick_list(others, None::<Format>, move |f| Message::LibraryExport(export_path.clone(), f))
+                        .placeholder(tr("ui.export_as")),
+                    button(text(tr(delete))).on_press(Message::LibraryDelete(path)),
+                ]
+                .spacing(8)
+                .align_items(iced::Alignment::Center),
+            ]
+            .spacing(4)
+            .into()
+        }))
+        .spacing(16);
+        let list: Element<'_, Message> = if self.library.is_empty() {
+            text(tr("ui.library_empty")).into()
+        } else {
+            scrollable(entries).height(Length::Fill).into()
+        };
+        let file = library::dir().map(|d| d.join("library.json").display().to_string()).unwrap_or_default();
+
+        let content = column![
+            row![text(tr("ui.library")).size(20), text(&self.status)].spacing(12).align_items(iced::Alignment::Center),
+            list,
+            text(lang.trf("ui.library_file", &[&file])).size(12),
+            button(text(tr("ui.back"))).on_press(Message::Show(Screen::Main)),
+        ]
+        .spacing(12)
+        .padding(12);
+        container(content).width(Length::Fill).height(Length::Fill).into()
     }
-    Ok(None)
 }
 
-fn parse_backend_to_markdown(body: &str, normalized_share: &str) -> Option<String> {
-    let title = Regex::new(r#""title"\s*:\s*"(.*?)""#)
-        .ok()?
-        .captures(body)
-        .and_then(|c| c.get(1))
-        .map(|m| json_unescape(m.as_str()));
-
-    let pattern = Regex::new(r#""role"\s*:\s*"(user|assistant)"[\s\S]*?"parts"\s*:\s*\[(.*?)\]"#).ok()?;
-    let mut msgs: Vec<(String, String)> = Vec::new();
-    for cap in pattern.captures_iter(body) {
-        let role = cap.get(1)?.as_str().to_string();
-        let parts_raw = format!("[{}]", cap.get(2)?.as_str());
-        let mut text = String::new();
-        if let Ok(vec) = serde_json::from_str::<Vec<String>>(&parts_raw) {
-            text = vec.join("\n\n");
-        } else {
-            text = json_unescape(cap.get(2)?.as_str());
+fn render(export: &Export, conv: &Conversation) -> Result<Vec<u8>> {
+    match export.format {
+        Format::Markdown if export.front_matter => {
+            Ok((conv.front_matter() + &export.template.render(conv, export)).into_bytes())
         }
-        msgs.push((role, text));
+        Format::Markdown => Ok(export.template.render(conv, export).into_bytes()),
+        Format::Pdf => pdf::render(conv, export.lang),
+        Format::Html => Ok(html::render(conv, export.lang).into_bytes()),
+        Format::Json => Ok(json::render(conv)?.into_bytes()),
     }
+}
 
-    if msgs.is_empty() {
-        return None;
+// Writes `docs[0]` to `path` and any further branches next to it. Markdown gets the
+// downloaded attachments in a folder beside it.
+fn save(export: &Export, docs: &[Conversation], path: &std::path::Path) -> Result<Vec<std::path::PathBuf>> {
+    let docs = match export.format {
+        Format::Markdown => assets::write(docs, path)?,
+        _ => docs.to_vec(),
+    };
+    let mut saved = Vec::new();
+    for (i, doc) in docs.iter().enumerate() {
+        let target = if i == 0 { path.to_path_buf() } else { branch_path(path, i + 1) };
+        std::fs::write(&target, render(export, doc)?)?;
+        // the file is written either way; an index that can't be updated only loses the entry
+        let _ = library::add(doc, &target, export);
+        saved.push(target);
     }
+    Ok(saved)
+}
 
-    let mut out = String::new();
-    out.push_str(&format!("# {}\n\n", title.unwrap_or_else(|| String::from("ChatGPT Conversation"))));
-    out.push_str(&format!("**Источник**: https://{}\n\n", normalized_share));
+// chat.md -> chat.branch-2.md
+fn branch_path(path: &std::path::Path, n: usize) -> std::path::PathBuf {
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let name = match path.extension() {
+        Some(ext) => format!("{}.branch-{}.{}", stem, n, ext.to_string_lossy()),
+        None => format!("{}.branch-{}", stem, n),
+    };
+    path.with_file_name(name)
+}
 
-    for (role, text_) in msgs {
-        let who = if role == "assistant" { "Ассистент" } else { "Пользователь" };
-        out.push_str(&format!("> {}: {}\n\n", who, text_.replace("\r\n", "\n")));
+#[tokio::main]
+async fn main() -> ExitCode {
+    // Any argument switches to the headless CLI; a bare launch opens the window.
+    if std::env::args_os().len() > 1 {
+        return cli::run().await;
+    }
+    match App::run(Settings::default()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
     }
-    Some(out)
 }
 
-fn extract_share_id(normalized: &str) -> Option<String> {
-    let re = Regex::new(r"/share/([a-f0-9\-]+)").ok()?;
-    re.captures(normalized)
-        .and_then(|c| c.get(1))
-        .map(|m| m.as_str().to_string())
+// With `assets`, images and files the conversation refers to are downloaded too.
+async fn fetch_and_convert(
+    share_url: String,
+    relays: Vec<Relay>,
+    assets: bool,
+    progress: Progress,
+) -> Result<Conversation> {
+    if share_url.trim().is_empty() {
+        return Err(FetchError::NoLink.into());
+    }
+    let Some(src) = source::for_url(&share_url) else {
+        return Err(FetchError::Unsupported.into());
+    };
+    let deadline = tokio::time::Instant::now() + http::TOTAL_TIMEOUT;
+    let fetch = src.fetch(share_url.trim(), &relays, &progress);
+    let Ok(conv) = tokio::time::timeout_at(deadline, fetch).await else {
+        return Err(FetchError::TimedOut(http::TOTAL_TIMEOUT).into());
+    };
+    let mut conv = conv?;
+    if assets {
+        fetch_assets(&mut conv, &relays, &progress, deadline).await;
+    }
+    Ok(conv)
 }
 
-fn json_unescape(s: &str) -> String {
-    serde_json::from_str::<String>(&format!("\"{}\"", s)).unwrap_or_else(|_| s.to_string())
+// Downloads the attachments of `conv`; those not in by `deadline` are written as
+// placeholders.
+async fn fetch_assets(conv: &mut Conversation, relays: &[Relay], progress: &Progress, deadline: tokio::time::Instant) {
+    if let Some(src) = source::for_url(&conv.source) {
+        let _ = tokio::time::timeout_at(deadline, src.fetch_assets(conv, relays, progress)).await;
+    }
 }
 
 fn read_clipboard_text() -> Option<String> {
@@ -315,6 +1313,55 @@ fn read_clipboard_text() -> Option<String> {
 }
 
 impl App {
+    fn export(&self) -> Export {
+        Export {
+            format: self.settings.format.clone(),
+            branches: self.branches.clone(),
+            hidden: self.hidden.clone(),
+            front_matter: self.front_matter,
+            lang: self.settings.output_language,
+            template: self.template.clone(),
+            citations: self.citations,
+            math: self.math,
+            naming: self.settings.naming.clone(),
+        }
+    }
+
+    fn fail(&mut self, error: &str) {
+        self.status = self.settings.ui_language.trf("st.error", &[&error]);
+        self.push_log(&self.status.clone());
+    }
+
+    // Saves once no further edit came for half a second, not on every keystroke.
+    fn save_settings_later(&mut self) -> Command<Message> {
+        self.settings_edits += 1;
+        let edit = self.settings_edits;
+        Command::perform(tokio::time::sleep(std::time::Duration::from_millis(500)), move |_| {
+            Message::SettingsTyped(edit)
+        })
+    }
+
+    fn save_settings(&mut self) {
+        if let Err(e) = self.settings.save() {
+            self.push_log(&self.settings.ui_language.trf("st.error", &[&e]));
+        }
+    }
+
+    // A file dialog opening in the folder last saved into.
+    fn dialog(&self) -> FileDialog {
+        match &self.settings.output_dir {
+            Some(dir) if dir.is_dir() => FileDialog::new().set_directory(dir),
+            _ => FileDialog::new(),
+        }
+    }
+
+    fn remember_dir(&mut self, dir: &std::path::Path) {
+        if self.settings.output_dir.as_deref() != Some(dir) {
+            self.settings.output_dir = Some(dir.to_path_buf());
+            self.save_settings();
+        }
+    }
+
     fn push_log(&mut self, line: &str) {
         self.logs.push(line.to_string());
         if self.logs.len() > 500 { // keep last 500 lines
@@ -0,0 +1,183 @@
+// Rewrites `\( x \)` to `$x$` and `\[ x \]` to `$$ x $$`, the delimiters GitHub,
+// Obsidian and most other Markdown renderers understand. Dollar math already in the
+// text stays; other dollars become `\$` so they don't pair up with the new ones. Fenced
+// code blocks and inline code are left as they are.
+pub fn to_dollars(text: &str) -> String {
+    let mut out = String::new();
+    let mut prose = String::new();
+    let mut fence: Option<String> = None;
+    for line in text.split_inclusive('\n') {
+        match &fence {
+            Some(open) => {
+                out.push_str(line);
+                if closes(line, open) {
+                    fence = None;
+                }
+            }
+            None => match opening_fence(line) {
+                Some(open) => {
+                    out.push_str(&convert_prose(&std::mem::take(&mut prose)));
+                    out.push_str(line);
+                    fence = Some(open);
+                }
+                None => prose.push_str(line),
+            },
+        }
+    }
+    out.push_str(&convert_prose(&prose));
+    out
+}
+
+// "```rust" -> "```", "~~~~" -> "~~~~"
+fn opening_fence(line: &str) -> Option<String> {
+    let line = line.trim_start();
+    let ch = line.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let len = line.len() - line.trim_start_matches(ch).len();
+    (len >= 3).then(|| ch.to_string().repeat(len))
+}
+
+// A run of the fence character at least as long as the opening one, and nothing else.
+fn closes(line: &str, open: &str) -> bool {
+    let line = line.trim();
+    line.len() >= open.len() && line.chars().all(|c| open.starts_with(c))
+}
+
+// Converts the text between inline code spans.
+fn convert_prose(text: &str) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('`') {
+        let ticks = run(&rest[start..]);
+        let after = start + ticks;
+        // a span ends at the next run of exactly as many backticks
+        let mut close = None;
+        let mut i = after;
+        while let Some(pos) = rest[i..].find('`') {
+            let len = run(&rest[i + pos..]);
+            if len == ticks {
+                close = Some(i + pos + len);
+                break;
+            }
+            i += pos + len;
+        }
+        let end = close.unwrap_or(after);
+        out.push_str(&convert_math(&rest[..start]));
+        out.push_str(&rest[start..end]);
+        rest = &rest[end..];
+    }
+    out.push_str(&convert_math(rest));
+    out
+}
+
+fn run(text: &str) -> usize {
+    text.len() - text.trim_start_matches('`').len()
+}
+
+fn convert_math(text: &str) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(at) = rest.find(['\\', '$']) {
+        out.push_str(&rest[..at]);
+        rest = &rest[at..];
+        if rest.starts_with('$') {
+            let (kept, len) = match dollar_math(rest) {
+                Some(len) => (&rest[..len], len),
+                None => ("\\$", 1),
+            };
+            out.push_str(kept);
+            rest = &rest[len..];
+            continue;
+        }
+        let (close, inline) = match rest[1..].chars().next() {
+            Some('(') => ("\\)", true),
+            Some('[') => ("\\]", false),
+            // `\\`, `\$`, ...: escapes stay as they are, with what they escape
+            next => {
+                let len = 1 + next.map_or(0, char::len_utf8);
+                out.push_str(&rest[..len]);
+                rest = &rest[len..];
+                continue;
+            }
+        };
+        let body = &rest[2..];
+        // inline math never spans a blank line
+        let Some(end) = body.find(close).filter(|&end| end > 0 && !(inline && body[..end].contains("\n\n"))) else {
+            out.push_str(&rest[..2]);
+            rest = body;
+            continue;
+        };
+        let math = &body[..end];
+        // `$a$$b$` would read as one span `a$$b`
+        if out.ends_with('$') && !out.ends_with("\\$") {
+            out.push(' ');
+        }
+        if inline {
+            // `$ x $` is not math to most renderers, so the spaces go
+            out.push_str(&format!("${}$", math.trim()));
+        } else {
+            out.push_str(&format!("$${}$$", math));
+        }
+        rest = &body[end + close.len()..];
+    }
+    out.push_str(rest);
+    out
+}
+
+// The length of the `$...$` or `$$...$$` math `text` starts with, if it does. Like
+// pandoc: `$` math has no space inside its delimiters and no digit right after it, so
+// "$5 and $10" is not math.
+fn dollar_math(text: &str) -> Option<usize> {
+    if let Some(body) = text.strip_prefix("$$") {
+        return body.find("$$").filter(|&end| end > 0).map(|end| end + 4);
+    }
+    let body = &text[1..];
+    if body.starts_with(|c: char| c.is_whitespace() || c == '$') {
+        return None;
+    }
+    let paragraph = &body[..body.find("\n\n").unwrap_or(body.len())];
+    paragraph.match_indices('$').map(|(end, _)| end).find_map(|end| {
+        let before = paragraph[..end].chars().next_back()?;
+        let after = paragraph[end + 1..].chars().next();
+        let closes = !before.is_whitespace() && before != '\\' && !after.is_some_and(|c| c.is_ascii_digit() || c == '$');
+        closes.then_some(end + 2)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::to_dollars;
+
+    #[test]
+    fn converts_inline_and_display_math() {
+        assert_eq!(to_dollars("so \\( x^2 \\) and\n\\[\na+b\n\\]\n"), "so $x^2$ and\n$$\na+b\n$$\n");
+    }
+
+    #[test]
+    fn converts_adjacent_spans() {
+        assert_eq!(to_dollars("\\(a\\)\\(b\\) \\[c\\]\\[d\\]"), "$a$ $b$ $$c$$ $$d$$");
+    }
+
+    #[test]
+    fn escapes_dollars_in_prose() {
+        assert_eq!(to_dollars("costs $5 and \\(x\\) is $10"), "costs \\$5 and $x$ is \\$10");
+        assert_eq!(to_dollars("costs $5 and $10"), "costs \\$5 and \\$10");
+        assert_eq!(to_dollars("already \\$5"), "already \\$5");
+    }
+
+    #[test]
+    fn keeps_dollar_math() {
+        assert_eq!(to_dollars("$x$ and $$y = 1$$ and \\(z\\)"), "$x$ and $$y = 1$$ and $z$");
+    }
+
+    #[test]
+    fn leaves_escapes_and_unclosed_spans() {
+        assert_eq!(to_dollars("\\\\(x\\) and \\( open"), "\\\\(x\\) and \\( open");
+        assert_eq!(to_dollars("\\(a\n\nb\\)"), "\\(a\n\nb\\)");
+    }
+
+    #[test]
+    fn leaves_code_alone() {
+        let text = "`\\(x\\) $1` \\(y\\)\n```\n\\(z\\) $2\n```\n\\(w\\)";
+        assert_eq!(to_dollars(text), "`\\(x\\) $1` $y$\n```\n\\(z\\) $2\n```\n$w$");
+    }
+}
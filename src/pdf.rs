@@ -0,0 +1,493 @@
+use crate::conversation::{Conversation, Turn};
+use crate::i18n::Lang;
+use anyhow::{Context, Result};
+use fontdb::{Database, Family, Query, Weight, ID};
+use printpdf::path::PaintMode;
+use printpdf::{Color, IndirectFontRef, Mm, PdfDocument, PdfDocumentReference, PdfLayerReference, Rect, Rgb};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+// A4, millimetres
+const PAGE_W: f32 = 210.0;
+const PAGE_H: f32 = 297.0;
+const MARGIN: f32 = 20.0;
+const PT: f32 = 25.4 / 72.0;
+
+const TITLE_SIZE: f32 = 18.0;
+const TURN_SIZE: f32 = 13.0;
+const HEADING_SIZE: f32 = 11.5;
+const BODY_SIZE: f32 = 10.5;
+const CODE_SIZE: f32 = 9.0;
+const SMALL_SIZE: f32 = 8.5;
+const VARIANT_INDENT: f32 = 6.0;
+
+const SANS_FAMILIES: &[&str] = &[
+    "Noto Sans",
+    "DejaVu Sans",
+    "Segoe UI",
+    "Arial",
+    "Helvetica Neue",
+    "Liberation Sans",
+];
+const MONO_FAMILIES: &[&str] = &[
+    "DejaVu Sans Mono",
+    "Noto Sans Mono",
+    "Consolas",
+    "Menlo",
+    "Liberation Mono",
+    "Courier New",
+];
+// Tried in order for characters the primary faces don't cover (mostly CJK).
+const FALLBACK_FAMILIES: &[&str] = &[
+    "Noto Sans CJK JP",
+    "Noto Sans CJK SC",
+    "Source Han Sans",
+    "Microsoft YaHei",
+    "PingFang SC",
+    "Hiragino Sans GB",
+    "WenQuanYi Micro Hei",
+    "Droid Sans Fallback",
+    "Malgun Gothic",
+    "Apple SD Gothic Neo",
+    "Yu Gothic",
+    "MS Gothic",
+    "Arial Unicode MS",
+];
+
+pub fn render(conv: &Conversation, lang: Lang) -> Result<Vec<u8>> {
+    let fonts = Fonts::load()?;
+    let (doc, page, layer) = PdfDocument::new(&conv.title, Mm(PAGE_W), Mm(PAGE_H), "Layer 1");
+    let layer = doc.get_page(page).get_layer(layer);
+    let mut w = Writer { doc, fonts, layers: vec![layer], y: PAGE_H - MARGIN, indent: 0.0 };
+
+    w.text(&conv.title, Style::Bold, TITLE_SIZE)?;
+    w.gap(1.0);
+    w.text(&conv.source, Style::Regular, SMALL_SIZE)?;
+    w.gap(4.0);
+
+    push_turns(&mut w, &conv.turns, lang)?;
+
+    w.finish()
+}
+
+fn push_turns(w: &mut Writer, turns: &[Turn], lang: Lang) -> Result<()> {
+    for turn in turns {
+        w.gap(3.0);
+        w.rule();
+        w.gap(1.5);
+        let label = match &turn.fork {
+            Some(fork) => format!("{} ({})", turn.label(lang), lang.trf("out.variant_inline", &[&fork.number, &fork.total])),
+            None => turn.label(lang),
+        };
+        w.text(&label, Style::Bold, TURN_SIZE)?;
+        w.gap(1.5);
+        let body = turn.cited_body(|n| n.iter().map(|k| format!("[{}]", k)).collect());
+        for block in blocks(&body) {
+            match block {
+                Block::Heading(s) => w.text(&s, Style::Bold, HEADING_SIZE)?,
+                Block::Paragraph(s) => w.text(&s, Style::Regular, BODY_SIZE)?,
+                Block::Code(s) => w.code(&s)?,
+            }
+            w.gap(2.0);
+        }
+        for attachment in &turn.attachments {
+            w.text(&attachment.placeholder(lang), Style::Regular, SMALL_SIZE)?;
+            w.gap(2.0);
+        }
+        if !turn.sources.is_empty() {
+            w.text(&format!("{}:", lang.tr("out.sources")), Style::Bold, SMALL_SIZE)?;
+            for (k, source) in (1..).zip(&turn.sources) {
+                w.text(&format!("{}. {} — {}", k, source.title, source.url), Style::Regular, SMALL_SIZE)?;
+            }
+            w.gap(2.0);
+        }
+        if let Some(fork) = &turn.fork {
+            for variant in &fork.alternatives {
+                w.indent += VARIANT_INDENT;
+                w.gap(2.0);
+                w.text(&lang.trf("out.variant", &[&variant.number, &fork.total]), Style::Bold, HEADING_SIZE)?;
+                push_turns(w, &variant.turns, lang)?;
+                w.indent -= VARIANT_INDENT;
+            }
+        }
+    }
+    Ok(())
+}
+
+enum Block {
+    Heading(String),
+    Paragraph(String),
+    Code(String),
+}
+
+// Splits message text into paragraphs, markdown headings and fenced code blocks.
+fn blocks(text: &str) -> Vec<Block> {
+    let mut out = Vec::new();
+    let mut para: Vec<&str> = Vec::new();
+    let mut code: Option<(&str, Vec<&str>)> = None;
+
+    for line in text.lines() {
+        if let Some((open, lines)) = code.as_mut() {
+            if line.trim_start().starts_with(*open) {
+                out.push(Block::Code(lines.join("\n")));
+                code = None;
+            } else {
+                lines.push(line);
+            }
+            continue;
+        }
+        let trimmed = line.trim_start();
+        let breaks = fence(trimmed).is_some() || trimmed.starts_with('#') || trimmed.is_empty();
+        if breaks && !para.is_empty() {
+            out.push(Block::Paragraph(para.join("\n")));
+            para.clear();
+        }
+        if let Some(open) = fence(trimmed) {
+            code = Some((open, Vec::new()));
+        } else if trimmed.starts_with('#') {
+            out.push(Block::Heading(trimmed.trim_start_matches('#').trim().to_string()));
+        } else if !trimmed.is_empty() {
+            para.push(line);
+        }
+    }
+    if let Some((_, lines)) = code {
+        out.push(Block::Code(lines.join("\n")));
+    }
+    if !para.is_empty() {
+        out.push(Block::Paragraph(para.join("\n")));
+    }
+    out
+}
+
+// "```rust" -> "```", "~~~~" -> "~~~~"; the block ends at the same fence.
+fn fence(line: &str) -> Option<&str> {
+    let c = line.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let run = &line[..line.find(|x| x != c).unwrap_or(line.len())];
+    (run.len() >= 3).then_some(run)
+}
+
+#[derive(Clone, Copy)]
+enum Style {
+    Regular,
+    Bold,
+    Mono,
+}
+
+struct Face {
+    data: Vec<u8>,
+    units_per_em: f32,
+    advances: RefCell<HashMap<char, Option<u16>>>,
+    pdf: Option<IndirectFontRef>,
+}
+
+impl Face {
+    fn load(db: &Database, id: ID) -> Option<Face> {
+        db.with_face_data(id, |data, index| {
+            // printpdf always reads the first face of a collection
+            if index != 0 {
+                return None;
+            }
+            let face = ttf_parser::Face::parse(data, 0).ok()?;
+            Some(Face {
+                data: data.to_vec(),
+                units_per_em: face.units_per_em() as f32,
+                advances: RefCell::new(HashMap::new()),
+                pdf: None,
+            })
+        })
+        .flatten()
+    }
+
+    fn advance(&self, c: char) -> Option<u16> {
+        *self.advances.borrow_mut().entry(c).or_insert_with(|| {
+            let face = ttf_parser::Face::parse(&self.data, 0).ok()?;
+            let gid = face.glyph_index(c)?;
+            Some(face.glyph_hor_advance(gid).unwrap_or(0))
+        })
+    }
+}
+
+// Scanning the installed fonts takes a while; it is done once per run.
+fn system_fonts() -> &'static Database {
+    static DB: OnceLock<Database> = OnceLock::new();
+    DB.get_or_init(|| {
+        let mut db = Database::new();
+        db.load_system_fonts();
+        db
+    })
+}
+
+struct Fonts {
+    db: &'static Database,
+    faces: Vec<Face>,
+    loaded: HashMap<ID, Option<usize>>,
+    regular: usize,
+    bold: usize,
+    mono: usize,
+    fallbacks: Option<Vec<usize>>,
+    by_char: HashMap<char, Option<usize>>,
+}
+
+impl Fonts {
+    fn load() -> Result<Self> {
+        let mut fonts = Fonts {
+            db: system_fonts(),
+            faces: Vec::new(),
+            loaded: HashMap::new(),
+            regular: 0,
+            bold: 0,
+            mono: 0,
+            fallbacks: None,
+            by_char: HashMap::new(),
+        };
+        fonts.regular = fonts
+            .first_of(SANS_FAMILIES, Family::SansSerif, Weight::NORMAL)
+            .or_else(|| fonts.covering('Ж'))
+            .context("No usable font found for PDF export (install e.g. Noto Sans or DejaVu Sans)")?;
+        fonts.bold = fonts
+            .first_of(SANS_FAMILIES, Family::SansSerif, Weight::BOLD)
+            .unwrap_or(fonts.regular);
+        fonts.mono = fonts
+            .first_of(MONO_FAMILIES, Family::Monospace, Weight::NORMAL)
+            .unwrap_or(fonts.regular);
+        Ok(fonts)
+    }
+
+    fn face_by_id(&mut self, id: ID) -> Option<usize> {
+        if let Some(idx) = self.loaded.get(&id) {
+            return *idx;
+        }
+        let idx = Face::load(self.db, id).map(|face| {
+            self.faces.push(face);
+            self.faces.len() - 1
+        });
+        self.loaded.insert(id, idx);
+        idx
+    }
+
+    fn query(&mut self, family: Family<'_>, weight: Weight) -> Option<usize> {
+        let id = self.db.query(&Query { families: &[family], weight, ..Query::default() })?;
+        self.face_by_id(id)
+    }
+
+    fn first_of(&mut self, names: &[&str], generic: Family<'_>, weight: Weight) -> Option<usize> {
+        names
+            .iter()
+            .find_map(|name| self.query(Family::Name(name), weight))
+            .or_else(|| self.query(generic, weight))
+    }
+
+    // Last resort: any installed face that has a glyph for `c`. Faces are only read to
+    // check; just the one found is kept.
+    fn covering(&mut self, c: char) -> Option<usize> {
+        let db = self.db;
+        let id = db.faces().map(|f| f.id).find(|&id| {
+            db.with_face_data(id, |data, index| {
+                index == 0 && ttf_parser::Face::parse(data, 0).is_ok_and(|face| face.glyph_index(c).is_some())
+            })
+            .unwrap_or(false)
+        })?;
+        self.face_by_id(id)
+    }
+
+    fn face_for(&mut self, style: Style, c: char) -> usize {
+        let primary = match style {
+            Style::Regular => self.regular,
+            Style::Bold => self.bold,
+            Style::Mono => self.mono,
+        };
+        if c.is_whitespace() || self.faces[primary].advance(c).is_some() {
+            return primary;
+        }
+        if let Some(idx) = self.by_char.get(&c) {
+            return idx.unwrap_or(primary);
+        }
+        if self.fallbacks.is_none() {
+            let mut list = vec![self.regular];
+            for name in FALLBACK_FAMILIES {
+                if let Some(idx) = self.query(Family::Name(name), Weight::NORMAL) {
+                    list.push(idx);
+                }
+            }
+            self.fallbacks = Some(list);
+        }
+        let found = self
+            .fallbacks
+            .as_ref()
+            .and_then(|list| list.iter().copied().find(|&idx| self.faces[idx].advance(c).is_some()))
+            .or_else(|| self.covering(c));
+        self.by_char.insert(c, found);
+        found.unwrap_or(primary)
+    }
+
+    // Width in millimetres of `c` rendered in face `idx` at `size` points.
+    fn width(&self, idx: usize, c: char, size: f32) -> f32 {
+        let face = &self.faces[idx];
+        let units = face.advance(c).or_else(|| face.advance(' ')).unwrap_or(0) as f32;
+        units / face.units_per_em * size * PT
+    }
+}
+
+struct Writer {
+    doc: PdfDocumentReference,
+    fonts: Fonts,
+    layers: Vec<PdfLayerReference>,
+    y: f32,
+    indent: f32,
+}
+
+impl Writer {
+    fn layer(&self) -> &PdfLayerReference {
+        self.layers.last().expect("document has at least one page")
+    }
+
+    fn new_page(&mut self) {
+        let (page, layer) = self.doc.add_page(Mm(PAGE_W), Mm(PAGE_H), "Layer 1");
+        self.layers.push(self.doc.get_page(page).get_layer(layer));
+        self.y = PAGE_H - MARGIN;
+    }
+
+    fn ensure(&mut self, height: f32) {
+        if self.y - height < MARGIN {
+            self.new_page();
+        }
+    }
+
+    fn gap(&mut self, mm: f32) {
+        self.y -= mm;
+    }
+
+    fn rule(&mut self) {
+        self.ensure(1.0);
+        self.fill(0.8);
+        self.layer().add_rect(
+            Rect::new(Mm(MARGIN + self.indent), Mm(self.y - 0.2), Mm(PAGE_W - MARGIN), Mm(self.y)).with_mode(PaintMode::Fill),
+        );
+        self.fill(0.0);
+        self.y -= 0.2;
+    }
+
+    fn fill(&self, gray: f32) {
+        self.layer().set_fill_color(Color::Rgb(Rgb::new(gray, gray, gray, None)));
+    }
+
+    fn text(&mut self, text: &str, style: Style, size: f32) -> Result<()> {
+        for line in self.wrap(text, style, size, PAGE_W - 2.0 * MARGIN - self.indent, false) {
+            let height = size * 1.35 * PT;
+            self.ensure(height);
+            self.draw(&line, style, size, MARGIN + self.indent)?;
+            self.y -= height;
+        }
+        Ok(())
+    }
+
+    fn code(&mut self, text: &str) -> Result<()> {
+        let pad = 2.0;
+        let height = CODE_SIZE * 1.3 * PT;
+        let text = text.replace('\t', "    ");
+        for line in self.wrap(&text, Style::Mono, CODE_SIZE, PAGE_W - 2.0 * MARGIN - self.indent - 2.0 * pad, true) {
+            self.ensure(height);
+            self.fill(0.94);
+            self.layer().add_rect(
+                Rect::new(Mm(MARGIN + self.indent), Mm(self.y - height), Mm(PAGE_W - MARGIN), Mm(self.y))
+                    .with_mode(PaintMode::Fill),
+            );
+            self.fill(0.0);
+            self.draw(&line, Style::Mono, CODE_SIZE, MARGIN + self.indent + pad)?;
+            self.y -= height;
+        }
+        Ok(())
+    }
+
+    // Draws one line with its top edge at the current cursor, switching faces per run.
+    fn draw(&mut self, line: &str, style: Style, size: f32, x: f32) -> Result<()> {
+        let baseline = self.y - size * PT;
+        let mut x = x;
+        let mut runs: Vec<(usize, String)> = Vec::new();
+        for c in line.chars() {
+            let idx = self.fonts.face_for(style, c);
+            match runs.last_mut() {
+                Some((last, run)) if *last == idx => run.push(c),
+                _ => runs.push((idx, c.to_string())),
+            }
+        }
+        for (idx, run) in runs {
+            let font = self.pdf_font(idx)?;
+            let width: f32 = run.chars().map(|c| self.fonts.width(idx, c, size)).sum();
+            self.layer().use_text(run, size, Mm(x), Mm(baseline), &font);
+            x += width;
+        }
+        Ok(())
+    }
+
+    fn pdf_font(&mut self, idx: usize) -> Result<IndirectFontRef> {
+        if let Some(font) = &self.fonts.faces[idx].pdf {
+            return Ok(font.clone());
+        }
+        let font = self
+            .doc
+            .add_external_font(self.fonts.faces[idx].data.as_slice())
+            .context("Failed to embed font")?;
+        self.fonts.faces[idx].pdf = Some(font.clone());
+        Ok(font)
+    }
+
+    fn wrap(&mut self, text: &str, style: Style, size: f32, max: f32, hard: bool) -> Vec<String> {
+        let mut lines = Vec::new();
+        for raw in text.split('\n') {
+            let mut line = String::new();
+            let mut width = 0.0;
+            let words: Vec<&str> = if hard { vec![raw] } else { raw.split_inclusive(' ').collect() };
+            for word in words {
+                let w: f32 = word
+                    .chars()
+                    .map(|c| {
+                        let idx = self.fonts.face_for(style, c);
+                        self.fonts.width(idx, c, size)
+                    })
+                    .sum();
+                if width + w <= max {
+                    line.push_str(word);
+                    width += w;
+                    continue;
+                }
+                if !hard && !line.is_empty() && w <= max {
+                    lines.push(std::mem::take(&mut line).trim_end().to_string());
+                    line.push_str(word);
+                    width = w;
+                    continue;
+                }
+                for c in word.chars() {
+                    let idx = self.fonts.face_for(style, c);
+                    let cw = self.fonts.width(idx, c, size);
+                    if width + cw > max && !line.is_empty() {
+                        lines.push(std::mem::take(&mut line));
+                        width = 0.0;
+                    }
+                    line.push(c);
+                    width += cw;
+                }
+            }
+            lines.push(if hard { line } else { line.trim_end().to_string() });
+        }
+        lines
+    }
+
+    fn finish(mut self) -> Result<Vec<u8>> {
+        let total = self.layers.len();
+        for n in 0..total {
+            let label = format!("{} / {}", n + 1, total);
+            let width: f32 = label
+                .chars()
+                .map(|c| {
+                    let idx = self.fonts.face_for(Style::Regular, c);
+                    self.fonts.width(idx, c, SMALL_SIZE)
+                })
+                .sum();
+            let font = self.pdf_font(self.fonts.regular)?;
+            self.layers[n].use_text(label, SMALL_SIZE, Mm((PAGE_W - width) / 2.0), Mm(MARGIN / 2.0), &font);
+        }
+        self.doc.save_to_bytes().context("Failed to write PDF")
+    }
+}
@@ -0,0 +1,56 @@
+use crate::i18n::Lang;
+use std::sync::Arc;
+
+// What a fetch is doing, as it happens; the window shows these in the status line, the
+// log and the progress bar. The command line doesn't listen.
+#[derive(Clone, Debug)]
+pub enum Event {
+    // trying `Stage` through the relay
+    Attempt(Stage, String),
+    Succeeded(Stage, String),
+    Failed(Stage, String, String),
+    // body bytes received so far, and the total when the server says
+    Bytes(u64, Option<u64>),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Stage {
+    // the share API, one of the candidate addresses
+    Api(usize, usize),
+    // the share page, for the payload embedded in it
+    PageData,
+    // the relay's text rendering of the page, the last resort
+    PageText,
+    Attachment(usize, usize),
+}
+
+impl Stage {
+    pub fn label(self, lang: Lang) -> String {
+        match self {
+            Stage::Api(n, of) => lang.trf("st.stage_api", &[&n, &of]),
+            Stage::PageData => lang.tr("st.stage_page_data").to_string(),
+            Stage::PageText => lang.tr("st.stage_page_text").to_string(),
+            Stage::Attachment(n, of) => lang.trf("st.stage_attachment", &[&n, &of]),
+        }
+    }
+}
+
+// Where a fetch sends its events; `none()` drops them.
+#[derive(Clone, Default)]
+pub struct Progress(Option<Arc<dyn Fn(Event) + Send + Sync>>);
+
+impl Progress {
+    pub fn new(report: impl Fn(Event) + Send + Sync + 'static) -> Progress {
+        Progress(Some(Arc::new(report)))
+    }
+
+    pub fn none() -> Progress {
+        Progress(None)
+    }
+
+    pub fn send(&self, event: Event) {
+        if let Some(report) = &self.0 {
+            report(event);
+        }
+    }
+}
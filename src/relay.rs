@@ -0,0 +1,86 @@
+use std::fmt;
+use std::str::FromStr;
+
+pub const JINA_TEMPLATE: &str = "https://r.jina.ai/{url}";
+
+// How a chatgpt.com URL is reached. A template relay gets the target spliced in at
+// `{url}` (as is) or `{url_encoded}` (percent-encoded).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Relay {
+    Direct,
+    Template(String),
+}
+
+impl Relay {
+    pub fn jina() -> Relay {
+        Relay::Template(JINA_TEMPLATE.into())
+    }
+
+    pub fn wrap(&self, target: &str) -> String {
+        match self {
+            Relay::Direct => target.to_string(),
+            Relay::Template(t) => t
+                .replace("{url_encoded}", &urlencoding::encode(target))
+                .replace("{url}", target),
+        }
+    }
+
+    // What gets recorded in exports; `None` means chatgpt.com was contacted directly.
+    pub fn recorded(&self) -> Option<String> {
+        match self {
+            Relay::Direct => None,
+            Relay::Template(t) => Some(t.clone()),
+        }
+    }
+}
+
+impl fmt::Display for Relay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Relay::Direct => write!(f, "direct"),
+            Relay::Template(t) if t == JINA_TEMPLATE => write!(f, "jina"),
+            Relay::Template(t) => write!(f, "{}", t),
+        }
+    }
+}
+
+impl FromStr for Relay {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "direct" => Ok(Relay::Direct),
+            "jina" => Ok(Relay::jina()),
+            t if t.starts_with("http://") || t.starts_with("https://") => {
+                if t.contains("{url}") || t.contains("{url_encoded}") {
+                    Ok(Relay::Template(t.to_string()))
+                } else {
+                    // a bare prefix such as https://r.jina.ai/
+                    Ok(Relay::Template(format!("{}{{url}}", t)))
+                }
+            }
+            t => Err(format!("unknown relay '{}' (expected direct, jina or an http(s) URL template)", t)),
+        }
+    }
+}
+
+pub fn default_chain() -> Vec<Relay> {
+    vec![Relay::jina()]
+}
+
+// "direct, jina, https://my.relay/?u={url_encoded}" -> tried in that order
+pub fn parse_chain(s: &str) -> Result<Vec<Relay>, String> {
+    let chain = s
+        .split([',', '\n'])
+        .filter(|p| !p.trim().is_empty())
+        .map(str::parse)
+        .collect::<Result<Vec<Relay>, String>>()?;
+    if chain.is_empty() {
+        return Err("relay list is empty".into());
+    }
+    Ok(chain)
+}
+
+pub fn chain_to_string(chain: &[Relay]) -> String {
+    chain.iter().map(Relay::to_string).collect::<Vec<_>>().join(", ")
+}
@@ -0,0 +1,87 @@
+use crate::batch::DEFAULT_NAMING;
+use crate::i18n::Lang;
+use crate::{relay, watch, Format};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+// What the window remembers between launches, in `<config dir>/kraken/settings.json`
+// (`$XDG_CONFIG_HOME/kraken` on Linux). Unknown or missing fields take their
+// defaults, so older files keep loading.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub ui_language: Lang,
+    // labels inside exported files ("Source", role names, ...)
+    pub output_language: Lang,
+    // Markdown template file; the built-in one when unset
+    pub template: Option<PathBuf>,
+    pub format: Format,
+    // where save dialogs open: the folder last saved into
+    pub output_dir: Option<PathBuf>,
+    // relay chain as typed, e.g. "direct, jina"
+    pub relays: String,
+    pub theme: Theme,
+    // file names for suggested saves, batches and imports (see `batch::file_name`)
+    pub naming: String,
+    // minutes between checks of watched files
+    pub watch_minutes: u64,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        let lang = Lang::from_env();
+        Self {
+            ui_language: lang,
+            output_language: lang,
+            template: None,
+            format: Format::Markdown,
+            output_dir: None,
+            relays: relay::chain_to_string(&relay::default_chain()),
+            theme: Theme::Dark,
+            naming: DEFAULT_NAMING.to_string(),
+            watch_minutes: watch::DEFAULT_MINUTES,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    #[default]
+    Dark,
+    Light,
+}
+
+impl Theme {
+    pub const ALL: [Theme; 2] = [Theme::Dark, Theme::Light];
+
+    pub fn label(self, lang: Lang) -> &'static str {
+        match self {
+            Theme::Dark => lang.tr("ui.theme_dark"),
+            Theme::Light => lang.tr("ui.theme_light"),
+        }
+    }
+}
+
+impl Settings {
+    pub fn load() -> Settings {
+        path()
+            .and_then(|p| std::fs::read_to_string(p).ok())
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = path().ok_or_else(|| anyhow::anyhow!("no config directory on this system"))?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, serde_json::to_string_pretty(self)? + "\n")?;
+        Ok(())
+    }
+}
+
+pub fn path() -> Option<PathBuf> {
+    Some(dirs::config_dir()?.join("kraken").join("settings.json"))
+}
@@ -0,0 +1,630 @@
+use crate::conversation::{Attachment, Category, Citation, Conversation, Fork, Role, ToolUse, Turn, Variant, WebSource};
+use crate::hydration;
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+
+// Payload of `backend-api/share/<id>`, and also one entry of `conversations.json` in
+// the account data export. Only `mapping`/`linear_conversation` carry the messages;
+// everything is optional because the endpoint changes shape now and then.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct SharePayload {
+    pub title: Option<String>,
+    pub create_time: Option<f64>,
+    pub update_time: Option<f64>,
+    pub conversation_id: Option<String>,
+    // the data export's name for `conversation_id`
+    pub id: Option<String>,
+    pub current_node: Option<String>,
+    #[serde(deserialize_with = "nullable")]
+    pub mapping: HashMap<String, Node>,
+    #[serde(deserialize_with = "nullable")]
+    pub linear_conversation: Vec<Node>,
+    pub model: Option<ModelInfo>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ModelInfo {
+    pub slug: Option<String>,
+    pub title: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Node {
+    pub id: String,
+    pub message: Option<ShareMessage>,
+    pub parent: Option<String>,
+    #[serde(deserialize_with = "nullable")]
+    pub children: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ShareMessage {
+    pub id: String,
+    #[serde(deserialize_with = "nullable")]
+    pub author: Author,
+    pub create_time: Option<f64>,
+    pub update_time: Option<f64>,
+    #[serde(deserialize_with = "nullable")]
+    pub content: Content,
+    pub status: Option<String>,
+    pub end_turn: Option<bool>,
+    pub recipient: Option<String>,
+    #[serde(deserialize_with = "nullable")]
+    pub metadata: MessageMetadata,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Author {
+    pub role: String,
+    pub name: Option<String>,
+    pub metadata: Value,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Content {
+    pub content_type: String,
+    #[serde(deserialize_with = "nullable")]
+    pub parts: Vec<Part>,
+    // `code`, `execution_output` and friends carry their payload here instead of `parts`
+    pub text: Option<String>,
+    pub language: Option<String>,
+    // browsing results: `tether_browsing_display` has result/summary, `tether_quote`
+    // a quoted page; `system_error` names the failed tool in `name`
+    pub result: Option<String>,
+    pub summary: Option<String>,
+    pub title: Option<String>,
+    pub url: Option<String>,
+    pub name: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Part {
+    Text(String),
+    Other(Value),
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct MessageMetadata {
+    pub model_slug: Option<String>,
+    #[serde(deserialize_with = "nullable")]
+    pub is_visually_hidden_from_conversation: bool,
+    #[serde(deserialize_with = "nullable")]
+    pub attachments: Vec<AttachmentMeta>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+// A file the user uploaded with the message (`metadata.attachments`).
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct AttachmentMeta {
+    pub id: String,
+    pub name: Option<String>,
+    pub size: Option<u64>,
+    pub mime_type: Option<String>,
+}
+
+// An entry of `metadata.content_references`: what a private-use marker in the text
+// ("\u{e200}cite\u{e202}turn0search3\u{e201}") stands for. Search results come as
+// `grouped_webpages` with the pages in `items`; other kinds carry a plain-text `alt`.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+struct ContentReference {
+    #[serde(deserialize_with = "nullable")]
+    matched_text: String,
+    // character position of `matched_text`
+    start_idx: Option<usize>,
+    #[serde(rename = "type", deserialize_with = "nullable")]
+    kind: String,
+    #[serde(deserialize_with = "nullable")]
+    items: Vec<Page>,
+    #[serde(deserialize_with = "nullable")]
+    sources: Vec<Page>,
+    title: Option<String>,
+    url: Option<String>,
+    alt: Option<String>,
+}
+
+// An entry of the older `metadata.citations`, for a "【3†source】" mark at the given
+// character positions.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+struct CitationMeta {
+    start_ix: Option<usize>,
+    end_ix: Option<usize>,
+    #[serde(deserialize_with = "nullable")]
+    metadata: Page,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+struct Page {
+    title: Option<String>,
+    url: Option<String>,
+}
+
+impl Page {
+    fn source(&self) -> Option<WebSource> {
+        let url = self.url.clone().filter(|u| !u.is_empty())?;
+        let title = self.title.clone().filter(|t| !t.trim().is_empty()).unwrap_or_else(|| url.clone());
+        Some(WebSource { title, url })
+    }
+}
+
+// The non-text part an image takes in `content.parts`.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+struct ImagePart {
+    content_type: String,
+    asset_pointer: String,
+    size_bytes: Option<u64>,
+}
+
+impl SharePayload {
+    // The body may come wrapped by a relay (e.g. jina's "Title: ... Markdown Content:"
+    // preamble), so parsing starts at the first object and ignores trailing text.
+    pub fn parse(body: &str) -> Option<SharePayload> {
+        let start = body.find('{')?;
+        let mut stream = serde_json::Deserializer::from_str(&body[start..]).into_iter::<SharePayload>();
+        stream.next()?.ok()
+    }
+
+    // The same payload as embedded in the raw share page for hydration, found as the
+    // object that carries the messages (it sits under the share route's loader data).
+    pub fn from_page(html: &str) -> Option<SharePayload> {
+        let has_messages = |m: &serde_json::Map<String, Value>| {
+            m.get("mapping").and_then(Value::as_object).is_some_and(|m| !m.is_empty())
+                || m.get("linear_conversation").and_then(Value::as_array).is_some_and(|l| !l.is_empty())
+        };
+        hydration::documents(html)
+            .iter()
+            .filter_map(|doc| hydration::find(doc, &has_messages))
+            .find_map(|v| serde_json::from_value(v.clone()).ok())
+    }
+
+    pub fn into_conversation(self, source: String) -> Option<Conversation> {
+        let turns = self.turns();
+        if turns.is_empty() {
+            return None;
+        }
+        let mut conv = Conversation::new(
+            self.title.unwrap_or_else(|| String::from("ChatGPT Conversation")),
+            source,
+            turns,
+        );
+        conv.created = self.create_time;
+        conv.updated = self.update_time;
+        Some(conv)
+    }
+
+    // The thread the author shared: the path from the root of `mapping` down to
+    // `current_node`. Turns where the author edited or regenerated carry the other
+    // siblings as a `Fork`; callers that want the plain thread drop those.
+    pub fn turns(&self) -> Vec<Turn> {
+        let path = self.current_path();
+        if !path.is_empty() {
+            return self.path_turns(&path, &mut HashSet::new());
+        }
+        self.linear_conversation
+            .iter()
+            .filter_map(|n| n.message.as_ref()?.to_turn())
+            .collect()
+    }
+
+    fn current_path(&self) -> Vec<&Node> {
+        let leaf = self
+            .current_node
+            .as_deref()
+            .and_then(|id| self.mapping.get(id))
+            .or_else(|| self.root().and_then(|root| self.newest_path(root).pop()));
+        let mut path = Vec::new();
+        let mut cursor = leaf;
+        while let Some(node) = cursor {
+            // a malformed mapping must not loop forever
+            if path.len() > self.mapping.len() {
+                return Vec::new();
+            }
+            path.push(node);
+            cursor = node.parent.as_deref().and_then(|id| self.mapping.get(id));
+        }
+        path.reverse();
+        path
+    }
+
+    fn root(&self) -> Option<&Node> {
+        self.mapping
+            .values()
+            .find(|n| n.parent.as_deref().is_none_or(|p| !self.mapping.contains_key(p)))
+    }
+
+    // `node` followed by the newest child at every level.
+    fn newest_path<'a>(&'a self, node: &'a Node) -> Vec<&'a Node> {
+        let mut path = vec![node];
+        let mut node = node;
+        while path.len() <= self.mapping.len() {
+            match node.children.last().and_then(|id| self.mapping.get(id)) {
+                Some(child) => {
+                    path.push(child);
+                    node = child;
+                }
+                None => break,
+            }
+        }
+        path
+    }
+
+    // `seen` holds the nodes already turned into turns: a malformed mapping can lead a
+    // variant back into a thread taken before, and the variant ends there.
+    fn path_turns(&self, path: &[&Node], seen: &mut HashSet<*const Node>) -> Vec<Turn> {
+        let len = path.iter().position(|n| !seen.insert(*n as *const Node)).unwrap_or(path.len());
+        let path = &path[..len];
+        let mut turns = Vec::new();
+        let mut pending = None;
+        for (i, node) in path.iter().enumerate() {
+            if i > 0 {
+                // hidden/system nodes don't become turns; their fork moves to the next turn
+                if let Some(fork) = self.fork_at(path[i - 1], node, seen) {
+                    pending = Some(fork);
+                }
+            }
+            if let Some(mut turn) = node.message.as_ref().and_then(ShareMessage::to_turn) {
+                turn.fork = pending.take();
+                turns.push(turn);
+            }
+        }
+        turns
+    }
+
+    fn fork_at(&self, parent: &Node, chosen: &Node, seen: &mut HashSet<*const Node>) -> Option<Fork> {
+        if parent.children.len() < 2 {
+            return None;
+        }
+        let mut number = 0;
+        let mut total = 0;
+        let mut alternatives = Vec::new();
+        for node in parent.children.iter().filter_map(|id| self.mapping.get(id)) {
+            if std::ptr::eq(node, chosen) {
+                total += 1;
+                number = total;
+                continue;
+            }
+            let turns = self.path_turns(&self.newest_path(node), seen);
+            if turns.is_empty() {
+                continue;
+            }
+            total += 1;
+            alternatives.push(Variant { number: total, turns });
+        }
+        if alternatives.is_empty() {
+            return None;
+        }
+        Some(Fork { number, total, alternatives })
+    }
+}
+
+impl ShareMessage {
+    pub fn to_turn(&self) -> Option<Turn> {
+        // an assistant message addressed to anything but "all" is a tool call
+        let recipient = self.recipient.as_deref().filter(|r| *r != "all");
+        let (role, tool) = match (self.author.role.as_str(), recipient) {
+            ("user", _) => (Role::User, None),
+            ("assistant", None) => (Role::Assistant, None),
+            ("assistant", Some(to)) => (Role::Assistant, Some(self.tool_use(to, false))),
+            ("tool", _) => (Role::Tool, Some(self.tool_use(self.author.name.as_deref().unwrap_or("tool"), true))),
+            ("system", _) => (Role::System, None),
+            _ => return None,
+        };
+        if self.metadata.is_visually_hidden_from_conversation {
+            return None;
+        }
+        let (text, sources, citations) = cite(&self.text(), &self.metadata);
+        let attachments = self.attachments();
+        if text.trim().is_empty() && attachments.is_empty() {
+            return None;
+        }
+        let mut turn = Turn::new(role, text);
+        turn.attachments = attachments;
+        turn.tool = tool;
+        turn.sources = sources;
+        turn.citations = citations;
+        turn.id = Some(self.id.clone()).filter(|id| !id.is_empty());
+        turn.created = self.create_time;
+        turn.model = self.metadata.model_slug.clone();
+        Some(turn)
+    }
+
+    fn tool_use(&self, name: &str, output: bool) -> ToolUse {
+        let category = match name.split('.').next().unwrap_or_default() {
+            "python" | "jupyter" => Category::Code,
+            "browser" | "web" | "search" => Category::Browsing,
+            _ => Category::Tools,
+        };
+        let language = match self.content.language.as_deref() {
+            Some(lang) if lang != "unknown" => Some(lang.to_string()),
+            _ if category == Category::Code && self.content.content_type == "code" => Some("python".to_string()),
+            _ if self.text().trim_start().starts_with('{') => Some("json".to_string()),
+            _ => None,
+        };
+        ToolUse { name: name.to_string(), category, output, language: language.filter(|_| !output) }
+    }
+
+    pub fn text(&self) -> String {
+        let c = &self.content;
+        match c.content_type.as_str() {
+            "tether_quote" => {
+                let title = c.title.as_deref().or(c.url.as_deref()).unwrap_or_default();
+                let link = match &c.url {
+                    Some(url) => format!("[{}]({})", title, url),
+                    None => title.to_string(),
+                };
+                let quote: Vec<String> = c.text.iter().flat_map(|t| t.lines()).map(|l| format!("> {}", l)).collect();
+                return format!("{}\n\n{}", link, quote.join("\n")).trim().to_string();
+            }
+            "tether_browsing_display" => {
+                let parts: Vec<&str> = [&c.summary, &c.result].into_iter().flatten().map(|s| s.trim()).collect();
+                return parts.into_iter().filter(|s| !s.is_empty()).collect::<Vec<_>>().join("\n\n");
+            }
+            "system_error" => {
+                let text = c.text.clone().unwrap_or_default();
+                return match &c.name {
+                    Some(name) => format!("{}: {}", name, text),
+                    None => text,
+                };
+            }
+            _ => {}
+        }
+        let parts: Vec<&str> = self
+            .content
+            .parts
+            .iter()
+            .filter_map(|p| match p {
+                Part::Text(s) => Some(s.as_str()),
+                Part::Other(_) => None,
+            })
+            .collect();
+        if parts.is_empty() {
+            self.content.text.clone().unwrap_or_default()
+        } else {
+            parts.join("\n\n")
+        }
+    }
+
+    // Images from `parts` first, named after the upload they came from when there is
+    // one, then uploads that aren't shown inline (documents, spreadsheets, ...).
+    pub fn attachments(&self) -> Vec<Attachment> {
+        let uploads = &self.metadata.attachments;
+        let mut out = Vec::new();
+        let mut shown = Vec::new();
+        for part in &self.content.parts {
+            let Part::Other(value) = part else { continue };
+            let Ok(image) = serde_json::from_value::<ImagePart>(value.clone()) else { continue };
+            if image.content_type != "image_asset_pointer" || image.asset_pointer.is_empty() {
+                continue;
+            }
+            let id = file_id(&image.asset_pointer);
+            let upload = uploads.iter().find(|u| u.id == id);
+            shown.extend(upload.map(|u| u.id.as_str()));
+            out.push(Attachment {
+                image: true,
+                name: upload.and_then(|u| u.name.clone()).unwrap_or_else(|| id.to_string()),
+                size: image.size_bytes.or(upload.and_then(|u| u.size)),
+                mime: upload.and_then(|u| u.mime_type.clone()),
+                pointer: Some(image.asset_pointer.clone()),
+                ..Attachment::default()
+            });
+        }
+        for upload in uploads.iter().filter(|u| !u.id.is_empty() && !shown.contains(&u.id.as_str())) {
+            out.push(Attachment {
+                image: upload.mime_type.as_deref().is_some_and(|m| m.starts_with("image/")),
+                name: upload.name.clone().unwrap_or_else(|| upload.id.clone()),
+                size: upload.size,
+                mime: upload.mime_type.clone(),
+                pointer: Some(format!("file-service://{}", upload.id)),
+                ..Attachment::default()
+            });
+        }
+        out
+    }
+}
+
+// What a citation mark is replaced with.
+enum Mark {
+    Cite(Vec<WebSource>),
+    Text(String),
+}
+
+// Takes the citation marks out of `text`, returning the clean text, the cited pages
+// and where they are cited. Marks without a known meaning are dropped.
+fn cite(text: &str, metadata: &MessageMetadata) -> (String, Vec<WebSource>, Vec<Citation>) {
+    let entries = |key: &str| metadata.extra.get(key).and_then(Value::as_array).cloned().unwrap_or_default();
+    let mut spans: Vec<(usize, usize, Mark)> = Vec::new();
+    let mut sources: Vec<WebSource> = Vec::new();
+    let taken = |spans: &[(usize, usize, Mark)], start: usize, end: usize| {
+        spans.iter().any(|(s, e, _)| start < *e && *s < end)
+    };
+
+    let chars: Vec<usize> = text.char_indices().map(|(i, _)| i).chain([text.len()]).collect();
+    for entry in entries("content_references") {
+        let Ok(reference) = serde_json::from_value::<ContentReference>(entry) else { continue };
+        let mut pages: Vec<WebSource> = reference.items.iter().chain(&reference.sources).filter_map(Page::source).collect();
+        pages.extend(Page { title: reference.title.clone(), url: reference.url.clone() }.source());
+        if reference.kind == "sources_footnote" {
+            // the list of everything cited, at the very end: kept, but not as a mark
+            for page in pages {
+                if !sources.contains(&page) {
+                    sources.push(page);
+                }
+            }
+            continue;
+        }
+        let matched = reference.matched_text.as_str();
+        if matched.trim().is_empty() {
+            continue;
+        }
+        let at_index = reference
+            .start_idx
+            .and_then(|i| chars.get(i))
+            .filter(|&&start| text[start..].starts_with(matched))
+            .map(|&start| (start, start + matched.len()));
+        let found = at_index
+            .into_iter()
+            .chain(text.match_indices(matched).map(|(start, m)| (start, start + m.len())))
+            .find(|(start, end)| !taken(&spans, *start, *end));
+        let Some((start, end)) = found else { continue };
+        let mark = match pages.is_empty() {
+            true => Mark::Text(reference.alt.unwrap_or_default()),
+            false => Mark::Cite(pages),
+        };
+        spans.push((start, end, mark));
+    }
+
+    for entry in entries("citations") {
+        let Ok(citation) = serde_json::from_value::<CitationMeta>(entry) else { continue };
+        let (Some(start), Some(end)) = (citation.start_ix, citation.end_ix) else { continue };
+        let (Some(&start), Some(&end)) = (chars.get(start), chars.get(end)) else { continue };
+        if start < end && !taken(&spans, start, end) {
+            let pages = citation.metadata.source().into_iter().collect();
+            spans.push((start, end, Mark::Cite(pages)));
+        }
+    }
+    spans.sort_by_key(|(start, _, _)| *start);
+
+    let mut out = String::new();
+    let mut cited = Vec::new();
+    let mut numbered: Vec<WebSource> = Vec::new();
+    let mut at = 0;
+    for (start, end, mark) in spans {
+        out.push_str(&strip_marks(&text[at..start]));
+        match mark {
+            Mark::Text(alt) => out.push_str(&alt),
+            Mark::Cite(pages) if pages.is_empty() => {}
+            Mark::Cite(pages) => {
+                let numbers = pages
+                    .into_iter()
+                    .map(|page| match numbered.iter().position(|p| *p == page) {
+                        Some(i) => i + 1,
+                        None => {
+                            numbered.push(page);
+                            numbered.len()
+                        }
+                    })
+                    .collect();
+                // "text \u{e200}cite…\u{e201}." -> "text[1]."
+                out.truncate(out.trim_end_matches([' ', '\u{a0}']).len());
+                cited.push(Citation { offset: out.len(), sources: numbers });
+            }
+        }
+        at = end;
+    }
+    out.push_str(&strip_marks(&text[at..]));
+    // pages only listed at the end follow the cited ones
+    numbered.extend(sources.into_iter().filter(|page| !numbered.contains(page)).collect::<Vec<_>>());
+    (out, numbered, cited)
+}
+
+// Drops citation marks nothing explains: "\u{e200}…\u{e201}" and "【3†source】".
+pub fn strip_marks(text: &str) -> std::borrow::Cow<'_, str> {
+    static MARKS: OnceLock<regex::Regex> = OnceLock::new();
+    let marks = MARKS.get_or_init(|| regex::Regex::new(r"\x{e200}[^\x{e201}]*\x{e201}|【[^】†]*†[^】]*】").unwrap());
+    marks.replace_all(text, "")
+}
+
+// "file-service://file-abc" / "sediment://file_abc" -> the bare file id
+pub fn file_id(pointer: &str) -> &str {
+    pointer.split_once("://").map_or(pointer, |(_, id)| id)
+}
+
+// `null` where a container is expected is treated as empty rather than a parse error.
+fn nullable<'de, D, T>(d: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Ok(Option::<T>::deserialize(d)?.unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SharePayload;
+    use serde_json::{json, Value};
+
+    fn node(id: &str, role: &str, parent: Option<&str>, children: &[&str]) -> Value {
+        json!({
+            "id": id,
+            "message": {"id": id, "author": {"role": role}, "content": {"content_type": "text", "parts": [id]}},
+            "parent": parent,
+            "children": children,
+        })
+    }
+
+    // `q` was answered twice; `a1` also lists `q` as its child, leading back into the thread
+    fn forked(current_node: Option<&str>) -> SharePayload {
+        serde_json::from_value(json!({
+            "current_node": current_node,
+            "mapping": {
+                "root": {"id": "root", "children": ["q"]},
+                "q": node("q", "user", Some("root"), &["a1", "a2"]),
+                "a1": node("a1", "assistant", Some("q"), &["q"]),
+                "a2": node("a2", "assistant", Some("q"), &["more"]),
+                "more": node("more", "user", Some("a2"), &[]),
+            },
+        }))
+        .unwrap()
+    }
+
+    fn texts(payload: &SharePayload) -> Vec<String> {
+        payload.turns().into_iter().map(|t| t.text).collect()
+    }
+
+    #[test]
+    fn follows_current_node() {
+        assert_eq!(texts(&forked(Some("a1"))), ["q", "a1"]);
+        assert_eq!(texts(&forked(Some("more"))), ["q", "a2", "more"]);
+        // without one, the newest child at every level
+        assert_eq!(texts(&forked(None)), ["q", "a2", "more"]);
+    }
+
+    #[test]
+    fn forks_carry_the_other_answers() {
+        let turns = forked(Some("more")).turns();
+        assert!(turns[0].fork.is_none());
+        let fork = turns[1].fork.as_ref().unwrap();
+        assert_eq!((fork.number, fork.total), (2, 2));
+        assert_eq!(fork.alternatives.len(), 1);
+        let variant = &fork.alternatives[0];
+        assert_eq!(variant.number, 1);
+        // the cycle back to `q` ends the variant
+        assert_eq!(variant.turns.iter().map(|t| t.text.as_str()).collect::<Vec<_>>(), ["a1"]);
+
+        let turns = forked(Some("a1")).turns();
+        let fork = turns[1].fork.as_ref().unwrap();
+        assert_eq!((fork.number, fork.total), (1, 2));
+        let variant = &fork.alternatives[0];
+        assert_eq!(variant.turns.iter().map(|t| t.text.as_str()).collect::<Vec<_>>(), ["a2", "more"]);
+    }
+
+    #[test]
+    fn parent_cycles_fall_back_to_the_linear_thread() {
+        let payload: SharePayload = serde_json::from_value(json!({
+            "current_node": "x",
+            "mapping": {
+                "x": node("x", "user", Some("y"), &["y"]),
+                "y": node("y", "assistant", Some("x"), &["x"]),
+            },
+            "linear_conversation": [node("x", "user", None, &[])],
+        }))
+        .unwrap();
+        assert!(payload.current_path().is_empty());
+        assert_eq!(texts(&payload), ["x"]);
+    }
+}
@@ -0,0 +1,92 @@
+mod chatgpt;
+
+use crate::conversation::Conversation;
+use crate::i18n::Lang;
+use crate::progress::Progress;
+use crate::relay::Relay;
+use anyhow::Result;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+// A chat provider whose public share links Kraken can save. Adding a provider means
+// implementing this and listing it in `SOURCES`; the GUI and CLI only go through here.
+pub trait Source: Sync {
+    fn name(&self) -> &'static str;
+
+    fn matches(&self, url: &str) -> bool;
+
+    // Reports each attempt (endpoint, relay, outcome) and the bytes received to `progress`.
+    fn fetch<'a>(
+        &'a self,
+        url: &'a str,
+        relays: &'a [Relay],
+        progress: &'a Progress,
+    ) -> BoxFuture<'a, Result<Conversation>>;
+
+    // Turns a payload the provider serves for `url` into a conversation; `None` when
+    // the body is not something this provider understands.
+    fn parse(&self, url: &str, body: &str) -> Option<Conversation>;
+
+    // Downloads what the conversation's attachments point to, filling in their `data`.
+    // Whatever can't be retrieved stays empty and is exported as a placeholder.
+    fn fetch_assets<'a>(
+        &'a self,
+        _conv: &'a mut Conversation,
+        _relays: &'a [Relay],
+        _progress: &'a Progress,
+    ) -> BoxFuture<'a, ()> {
+        Box::pin(async {})
+    }
+}
+
+static SOURCES: &[&dyn Source] = &[&chatgpt::ChatGpt];
+
+pub fn for_url(url: &str) -> Option<&'static dyn Source> {
+    SOURCES.iter().copied().find(|s| s.matches(url.trim()))
+}
+
+pub fn names() -> Vec<&'static str> {
+    SOURCES.iter().map(|s| s.name()).collect()
+}
+
+// Why a conversation couldn't be fetched. The window shows these in its own language;
+// elsewhere they read in English.
+#[derive(Debug)]
+pub enum FetchError {
+    NoLink,
+    Unsupported,
+    // what every relay and fallback answered, one line each
+    Failed(Vec<String>),
+    TimedOut(Duration),
+}
+
+impl FetchError {
+    pub fn message(&self, lang: Lang) -> String {
+        match self {
+            FetchError::NoLink => lang.tr("err.no_link").to_string(),
+            FetchError::Unsupported => lang.trf("err.unsupported", &[&names().join(", ")]),
+            FetchError::Failed(errors) => lang.trf("err.not_fetched", &[&errors.join("\n")]),
+            FetchError::TimedOut(after) => lang.trf("err.timed_out", &[&after.as_secs()]),
+        }
+    }
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message(Lang::En))
+    }
+}
+
+impl std::error::Error for FetchError {}
+
+// `error` with its causes, a `FetchError` in `lang`.
+pub fn error_text(error: &anyhow::Error, lang: Lang) -> String {
+    match error.downcast_ref::<FetchError>() {
+        Some(e) => e.message(lang),
+        None => format!("{:#}", error),
+    }
+}
@@ -0,0 +1,304 @@
+use super::{BoxFuture, FetchError, Source};
+use crate::conversation::{Conversation, Role, Turn};
+use crate::http;
+use crate::progress::{Event, Progress, Stage};
+use crate::relay::Relay;
+use crate::share::{self, SharePayload};
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub struct ChatGpt;
+
+impl Source for ChatGpt {
+    fn name(&self) -> &'static str {
+        "ChatGPT"
+    }
+
+    fn matches(&self, url: &str) -> bool {
+        let rest = url.trim_start_matches("https://").trim_start_matches("http://");
+        let rest = rest.trim_start_matches("www.");
+        (rest.starts_with("chatgpt.com/") || rest.starts_with("chat.openai.com/")) && rest.contains("/share/")
+    }
+
+    fn fetch<'a>(
+        &'a self,
+        url: &'a str,
+        relays: &'a [Relay],
+        progress: &'a Progress,
+    ) -> BoxFuture<'a, Result<Conversation>> {
+        Box::pin(fetch(url, relays, progress))
+    }
+
+    fn parse(&self, url: &str, body: &str) -> Option<Conversation> {
+        // backend-api JSON, or the share page with the same payload embedded
+        let normalized = url.trim_start_matches("https://").trim_start_matches("http://");
+        conversation(SharePayload::parse(body), normalized)
+            .or_else(|| conversation(SharePayload::from_page(body), normalized))
+    }
+
+    fn fetch_assets<'a>(
+        &'a self,
+        conv: &'a mut Conversation,
+        relays: &'a [Relay],
+        progress: &'a Progress,
+    ) -> BoxFuture<'a, ()> {
+        Box::pin(fetch_assets(conv, relays, progress))
+    }
+}
+
+async fn fetch(share_url: &str, relays: &[Relay], progress: &Progress) -> Result<Conversation> {
+    let normalized = share_url
+        .trim()
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .to_string();
+
+    let client = http::client()?;
+    let mut errors = Vec::new();
+
+    for relay in relays {
+        match try_fetch_backend_json(&client, relay, &normalized, progress).await {
+            Ok(mut conv) => {
+                conv.relay = relay.recorded();
+                return Ok(conv);
+            }
+            Err(e) => errors.push(format!("{}: {}", relay, e)),
+        }
+    }
+
+    // The raw share page embeds the same payload for hydration. Relays that render
+    // pages (jina) are asked for the HTML rather than their Markdown rendering.
+    for relay in relays {
+        progress.send(Event::Attempt(Stage::PageData, relay.to_string()));
+        let result = fetch_page(&client, relay, &normalized, true, progress).await.and_then(|html| {
+            conversation(SharePayload::from_page(&html), &normalized).context("no conversation in the page")
+        });
+        match report(progress, Stage::PageData, relay, result) {
+            Ok(mut conv) => {
+                conv.relay = relay.recorded();
+                return Ok(conv);
+            }
+            Err(e) => errors.push(format!("{} (page data): {}", relay, e)),
+        }
+    }
+
+    // Last resort: the relay's Markdown rendering of the page (jina-style), which keeps
+    // the text but not much structure; a direct fetch has nothing like it.
+    for relay in relays.iter().filter(|r| **r != Relay::Direct) {
+        progress.send(Event::Attempt(Stage::PageText, relay.to_string()));
+        match report(progress, Stage::PageText, relay, fetch_page(&client, relay, &normalized, false, progress).await) {
+            Ok(text) => {
+                let mut conv = Conversation::new(page_title(&text), share_url.to_string(), parse_page_turns(&text));
+                conv.share_id = extract_share_id(&normalized);
+                conv.relay = relay.recorded();
+                return Ok(conv);
+            }
+            Err(e) => errors.push(format!("{} (page): {}", relay, e)),
+        }
+    }
+
+    Err(FetchError::Failed(errors).into())
+}
+
+async fn fetch_assets(conv: &mut Conversation, relays: &[Relay], progress: &Progress) {
+    let Ok(client) = http::client() else {
+        return;
+    };
+    let mut pointers = Vec::new();
+    conv.attachments_mut(&mut |a| {
+        if let Some(p) = &a.pointer
+            && !pointers.contains(p)
+        {
+            pointers.push(p.clone());
+        }
+    });
+
+    // each file goes in as it arrives, so a fetch stopped halfway keeps what it got
+    let total = pointers.len();
+    for (i, pointer) in pointers.into_iter().enumerate() {
+        let stage = Stage::Attachment(i + 1, total);
+        let id = share::file_id(&pointer);
+        for relay in relays {
+            progress.send(Event::Attempt(stage, relay.to_string()));
+            let result = download_file(&client, relay, id, conv.share_id.as_deref(), progress).await;
+            if let Ok(data) = report(progress, stage, relay, result) {
+                let data = Arc::new(data);
+                conv.attachments_mut(&mut |a| {
+                    if a.pointer.as_ref() == Some(&pointer) {
+                        a.data = Some(data.clone());
+                    }
+                });
+                break;
+            }
+        }
+    }
+}
+
+// The files endpoint answers with a short-lived signed `download_url` on the file CDN;
+// the file itself is then fetched from there directly. The answer is looked up with a
+// regex so that relays wrapping the JSON in their own text still work.
+async fn download_file(
+    client: &reqwest::Client,
+    relay: &Relay,
+    id: &str,
+    share_id: Option<&str>,
+    progress: &Progress,
+) -> Result<Vec<u8>> {
+    let download_url = Regex::new(r#""download_url"\s*:\s*"([^"]+)""#).unwrap();
+    let mut endpoint = format!("https://chatgpt.com/backend-api/files/download/{}?inline=false", id);
+    if let Some(share_id) = share_id {
+        endpoint.push_str(&format!("&share_id={}", share_id));
+    }
+    let resp = http::send(client.get(relay.wrap(&endpoint))).await?.error_for_status()?;
+    let body = resp.text().await?;
+    let url = download_url
+        .captures(&body)
+        .map(|c| c[1].replace("\\u0026", "&").replace("\\/", "/"))
+        .context("no download link in the answer")?;
+    let resp = http::send(client.get(&url)).await?.error_for_status()?;
+    Ok(http::bytes(resp, progress).await?)
+}
+
+// Tells `progress` how an attempt ended.
+fn report<T>(progress: &Progress, stage: Stage, relay: &Relay, result: Result<T>) -> Result<T> {
+    match &result {
+        Ok(_) => progress.send(Event::Succeeded(stage, relay.to_string())),
+        Err(e) => progress.send(Event::Failed(stage, relay.to_string(), format!("{:#}", e))),
+    }
+    result
+}
+
+async fn fetch_page(
+    client: &reqwest::Client,
+    relay: &Relay,
+    normalized: &str,
+    raw: bool,
+    progress: &Progress,
+) -> Result<String> {
+    let cache_buster = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let sep = if normalized.contains('?') { '&' } else { '?' };
+    let url = relay.wrap(&format!("http://{}{}_ts={}", normalized, sep, cache_buster));
+
+    let mut request = client
+        .get(&url)
+        .header("Cache-Control", "no-cache")
+        .header("Pragma", "no-cache");
+    if raw && *relay != Relay::Direct {
+        request = request.header("X-Return-Format", "html");
+    }
+    let resp = http::send(request).await?.error_for_status()?;
+    Ok(http::text(resp, progress).await?)
+}
+
+fn page_title(text: &str) -> String {
+    Regex::new(r"^Title:\s*(.*)$")
+        .unwrap()
+        .captures(text)
+        .and_then(|c| c.get(1))
+        .map(|m| m.as_str().to_string())
+        .unwrap_or_else(|| "ChatGPT Conversation".to_string())
+}
+
+// Splits jina's page rendering into turns on its "You said:" / "ChatGPT said:" headings.
+fn parse_page_turns(text: &str) -> Vec<Turn> {
+    let marker = Regex::new(r"(?m)^#{1,6} (You|ChatGPT) said:[ \t]*$").unwrap();
+    let found: Vec<_> = marker.captures_iter(text).collect();
+    if found.is_empty() {
+        return vec![Turn::new(Role::Assistant, text.trim().to_string())];
+    }
+
+    let mut turns = Vec::new();
+    for (i, cap) in found.iter().enumerate() {
+        let whole = cap.get(0).unwrap();
+        let end = found.get(i + 1).map(|c| c.get(0).unwrap().start()).unwrap_or(text.len());
+        let role = if &cap[1] == "You" { Role::User } else { Role::Assistant };
+        turns.push(Turn::new(role, share::strip_marks(text[whole.end()..end].trim()).into_owned()));
+    }
+    turns
+}
+
+async fn try_fetch_backend_json(
+    client: &reqwest::Client,
+    relay: &Relay,
+    normalized_share: &str,
+    progress: &Progress,
+) -> Result<Conversation> {
+    let id = extract_share_id(normalized_share).unwrap_or_else(|| normalized_share.to_string());
+    let ts = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    let mut candidates = vec![format!("https://chatgpt.com/backend-api/share/{}?_ts={}", id, ts)];
+    if *relay != Relay::Direct {
+        // some relays only pass one of the schemes through
+        candidates.insert(0, format!("http://chatgpt.com/backend-api/share/{}?_ts={}", id, ts));
+    }
+
+    // a candidate that fails, even to connect, still leaves the next one to try
+    let mut errors = Vec::new();
+    let total = candidates.len();
+    for (i, u) in candidates.iter().enumerate() {
+        let stage = Stage::Api(i + 1, total);
+        let mut fail = |error: String| {
+            progress.send(Event::Failed(stage, relay.to_string(), error.clone()));
+            errors.push(error);
+        };
+        progress.send(Event::Attempt(stage, relay.to_string()));
+        let request = client
+            .get(relay.wrap(u))
+            .header("Cache-Control", "no-cache")
+            .header("Pragma", "no-cache");
+        let resp = match http::send(request).await {
+            Ok(resp) if resp.status().is_success() => resp,
+            Ok(resp) => {
+                fail(format!("HTTP {}", resp.status()));
+                continue;
+            }
+            Err(e) => {
+                fail(describe(&e));
+                continue;
+            }
+        };
+        let body = match http::text(resp, progress).await {
+            Ok(body) => body,
+            Err(e) => {
+                fail(describe(&e));
+                continue;
+            }
+        };
+        match ChatGpt.parse(normalized_share, &body) {
+            Some(conv) => {
+                progress.send(Event::Succeeded(stage, relay.to_string()));
+                return Ok(conv);
+            }
+            None => fail("no conversation in the response".into()),
+        }
+    }
+    anyhow::bail!("{}", errors.join("; "))
+}
+
+// reqwest's own message leaves out the cause ("connection refused", ...).
+fn describe(error: &reqwest::Error) -> String {
+    let mut text = error.to_string();
+    let mut source = std::error::Error::source(error);
+    while let Some(e) = source {
+        text.push_str(&format!(": {}", e));
+        source = e.source();
+    }
+    text
+}
+
+fn conversation(payload: Option<SharePayload>, normalized_share: &str) -> Option<Conversation> {
+    let mut conv = payload?.into_conversation(format!("https://{}", normalized_share))?;
+    conv.share_id = extract_share_id(normalized_share);
+    Some(conv)
+}
+
+fn extract_share_id(normalized: &str) -> Option<String> {
+    let re = Regex::new(r"/share/([a-f0-9\-]+)").ok()?;
+    re.captures(normalized)
+        .and_then(|c| c.get(1))
+        .map(|m| m.as_str().to_string())
+}
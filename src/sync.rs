@@ -0,0 +1,80 @@
+use crate::conversation::{Conversation, Turn};
+use crate::library::{self, Entry};
+use crate::progress::Progress;
+use crate::relay::Relay;
+use crate::template::Template;
+use crate::{assets, fetch_and_convert, fetch_assets, http, render, Branches, Export, Format};
+use anyhow::{Context, Result};
+
+// Brings a file from the library up to date: fetches the conversation again and adds
+// the turns written since it was saved, with the options the file was written with.
+// Markdown files get them appended, so edits made to the file are kept, laid out with
+// the file's template; other formats are written anew. Returns how many turns were added.
+pub async fn update(entry: Entry, export: Export, relays: Vec<Relay>) -> Result<usize> {
+    let old = library::snapshot(&entry)?;
+    let template = match &entry.template {
+        Some(path) => Template::load(path)?,
+        None => Template::default(),
+    };
+    let export = Export {
+        format: entry.format.clone(),
+        template,
+        branches: entry.branches.clone().unwrap_or(export.branches),
+        hidden: entry.hidden.clone().unwrap_or(export.hidden),
+        lang: entry.lang.unwrap_or(export.lang),
+        citations: entry.citations.unwrap_or(export.citations),
+        math: entry.math.unwrap_or(export.math),
+        ..export
+    };
+    let fresh = fetch_and_convert(entry.source.clone(), relays.clone(), false, Progress::none()).await?;
+    let fresh = fresh.hide(&export.hidden);
+    let added = Conversation { turns: new_turns(&old, &fresh)?, ..fresh };
+    // new branches of turns already in the file can't be shown without touching them
+    let mut added = if export.branches == Branches::Inline { added } else { added.current_thread() };
+    if added.turns.is_empty() {
+        return Ok(0);
+    }
+    // only the new turns' attachments are downloaded; the file has the others
+    if entry.format == Format::Markdown {
+        let deadline = tokio::time::Instant::now() + http::TOTAL_TIMEOUT;
+        fetch_assets(&mut added, &relays, &Progress::none(), deadline).await;
+    }
+
+    let merge = |added: &Conversation| Conversation {
+        turns: old.turns.iter().chain(&added.turns).cloned().collect(),
+        ..added.clone()
+    };
+    let added = match entry.format {
+        Format::Markdown => {
+            let added = assets::append(&[added], &entry.path, &old)?.remove(0);
+            let text = std::fs::read_to_string(&entry.path)
+                .with_context(|| format!("cannot read {} (export it again instead)", entry.path.display()))?;
+            std::fs::write(&entry.path, export.template.append(&text, &old, &added, &export))?;
+            added
+        }
+        _ => {
+            std::fs::write(&entry.path, render(&export, &merge(&added))?)?;
+            added
+        }
+    };
+    library::add(&merge(&added), &entry.path, &export)?;
+    Ok(added.turns.len())
+}
+
+// The turns of `fresh` after the last one `old` has, found by message id. Without ids
+// (the page text fallback has none) the shared thread is compared by position.
+fn new_turns(old: &Conversation, fresh: &Conversation) -> Result<Vec<Turn>> {
+    let Some(last) = old.turns.last() else {
+        return Ok(fresh.turns.clone());
+    };
+    let Some(id) = &last.id else {
+        if fresh.turns.len() < old.turns.len() {
+            anyhow::bail!("the conversation now has fewer turns than the file ({} < {})", fresh.turns.len(), old.turns.len());
+        }
+        return Ok(fresh.turns[old.turns.len()..].to_vec());
+    };
+    let after = |turns: &[Turn]| turns.iter().position(|t| t.id.as_ref() == Some(id)).map(|at| turns[at + 1..].to_vec());
+    // a file holding another branch goes on along that branch
+    let found = after(&fresh.turns).or_else(|| fresh.branches().iter().skip(1).find_map(|b| after(&b.turns)));
+    found.context("the last message of the file is no longer in the conversation; export it again instead")
+}
@@ -0,0 +1,352 @@
+use crate::conversation::{link, timestamp, Attachment, Conversation, Role, Turn, WebSource};
+use crate::i18n::Lang;
+use crate::{math, Citations, Export, Math};
+use anyhow::{Context, Result};
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use time::format_description::well_known::Rfc3339;
+
+// How Markdown exports are laid out. A template is plain text split into sections by
+// `[name]` lines; each section is written as is, newlines included, with `{{variable}}`
+// filled in. Sections a file leaves out are taken from this one.
+pub const DEFAULT: &str = "\
+[header]
+# {{title}}
+
+**{{label_source}}**: {{source_url}}
+
+[turn]
+> {{label}}: {{text}}
+
+[tool]
+> {{label}}:
+
+{{text}}
+
+[attachment]
+{{attachment}}
+
+[sources]
+**{{label_sources}}**
+
+{{sources}}
+
+[separator]
+[footer]
+[roles]
+";
+
+const SECTIONS: &[&str] = &["header", "turn", "tool", "attachment", "sources", "separator", "footer", "roles"];
+
+// Every variable is available in every section; turn and attachment ones are empty
+// outside of them.
+pub const VARIABLES: &[&str] = &[
+    // conversation
+    "title",
+    "source_url",
+    "share_id",
+    "create_time",
+    "update_time",
+    "fetched_at",
+    "models",
+    "message_count",
+    "relay",
+    "label_source",
+    "label_sources",
+    // turn
+    "index",
+    "role",
+    "role_id",
+    "label",
+    "text",
+    "model",
+    "message_id",
+    "time",
+    "tool",
+    // numbered list of the pages a turn cites
+    "sources",
+    // attachment
+    "attachment",
+    "attachment_name",
+    "attachment_path",
+];
+
+#[derive(Clone, Debug)]
+pub struct Template {
+    header: String,
+    // chat turns
+    turn: String,
+    // tool calls and their output; `text` is already a fenced block for code
+    tool: String,
+    attachment: String,
+    // after a turn that cites web pages
+    sources: String,
+    // between two turns
+    separator: String,
+    footer: String,
+    // role labels replacing the translated ones
+    roles: Vec<(Role, String)>,
+    // the file it was loaded from; the built-in template has none
+    pub path: Option<PathBuf>,
+}
+
+impl Default for Template {
+    fn default() -> Self {
+        Template::parse(DEFAULT).expect("the default template is valid")
+    }
+}
+
+impl Template {
+    pub fn load(path: &Path) -> Result<Template> {
+        let text = std::fs::read_to_string(path).with_context(|| format!("cannot read {}", path.display()))?;
+        let template = Template::parse(&text).with_context(|| format!("bad template {}", path.display()))?;
+        Ok(Template { path: Some(path.to_path_buf()), ..template })
+    }
+
+    pub fn parse(text: &str) -> Result<Template> {
+        let mut sections = split(DEFAULT);
+        for (name, body) in split(text) {
+            if name.is_empty() {
+                if !body.trim().is_empty() {
+                    anyhow::bail!("text before the first section (expected one of [{}])", SECTIONS.join("], ["));
+                }
+                continue;
+            }
+            if name != "roles"
+                && let Some(var) = variables(&body).find(|v| !VARIABLES.contains(v))
+            {
+                anyhow::bail!("unknown variable {{{{{}}}}} in [{}]", var, name);
+            }
+            sections.retain(|(n, _)| *n != name);
+            sections.push((name, body));
+        }
+        let section = |name: &str| {
+            sections.iter().find(|(n, _)| *n == name).map(|(_, body)| body.clone()).unwrap_or_default()
+        };
+        Ok(Template {
+            header: section("header"),
+            turn: section("turn"),
+            tool: section("tool"),
+            attachment: section("attachment"),
+            sources: section("sources"),
+            separator: section("separator"),
+            footer: section("footer"),
+            roles: roles(&section("roles"))?,
+            path: None,
+        })
+    }
+
+    pub fn render(&self, conv: &Conversation, export: &Export) -> String {
+        let (mut out, state) = self.body(conv, export);
+        out.push_str(&state.vars.fill(&self.footer));
+        out
+    }
+
+    // `text`, an export of `old` that may have been edited since, with the turns of
+    // `added` appended. Numbering and footnotes go on from `old`; the footer is
+    // rewritten when the file still ends with it.
+    pub fn append(&self, text: &str, old: &Conversation, added: &Conversation, export: &Export) -> String {
+        let (_, mut state) = self.body(old, export);
+        let old_footer = state.vars.fill(&self.footer);
+        let mut out = match text.strip_suffix(&old_footer) {
+            Some(body) if !old_footer.is_empty() => body.to_string(),
+            _ => text.to_string(),
+        };
+        let merged = Conversation { turns: old.turns.iter().chain(&added.turns).cloned().collect(), ..added.clone() };
+        set_conversation(&mut state.vars, &merged, export.lang);
+        if !old.turns.is_empty() {
+            out.push_str(&state.vars.fill(&self.separator));
+        }
+        self.push_turns(&mut out, &added.turns, old.turns.len() + 1, &mut state);
+        out.push_str(&state.vars.fill(&self.footer));
+        out
+    }
+
+    // Everything but the footer, and the variables it is filled with.
+    fn body(&self, conv: &Conversation, export: &Export) -> (String, State) {
+        let mut vars = Vars::default();
+        set_conversation(&mut vars, conv, export.lang);
+        let mut out = vars.fill(&self.header);
+        let mut state = State { lang: export.lang, citations: export.citations, math: export.math, vars, notes: 0 };
+        self.push_turns(&mut out, &conv.turns, 1, &mut state);
+        (out, state)
+    }
+
+    fn role_label(&self, role: &Role, lang: Lang) -> String {
+        match self.roles.iter().find(|(r, _)| r == role) {
+            Some((_, label)) => label.clone(),
+            None => role.label(lang).to_string(),
+        }
+    }
+
+    // `first` is the position of the first of `turns` in the thread; alternative
+    // branches count from the turn they replace.
+    fn push_turns(&self, out: &mut String, turns: &[Turn], first: usize, state: &mut State) {
+        let lang = state.lang;
+        for (i, turn) in turns.iter().enumerate() {
+            let vars = &mut state.vars;
+            if i > 0 {
+                out.push_str(&vars.fill(&self.separator));
+            }
+            if let Some(fork) = &turn.fork {
+                out.push_str(&format!("**{}**\n\n", lang.trf("out.variant", &[&fork.number, &fork.total])));
+            }
+            let role = self.role_label(&turn.role, lang);
+            vars.set("index", &(first + i).to_string());
+            vars.set("role", &role);
+            vars.set("role_id", turn.role.id());
+            vars.set("label", &turn.label_as(lang, &role));
+            // footnotes are numbered through the whole file
+            let notes = state.notes;
+            let text = match state.citations {
+                Citations::Footnotes => turn.cited_body(|n| n.iter().map(|k| format!("[^{}]", notes + k)).collect()),
+                Citations::Links => turn.cited_body(|n| {
+                    n.iter().map(|k| format!("[[{}]]({})", k, link(&turn.sources[k - 1].url))).collect()
+                }),
+            };
+            let text = match state.math {
+                Math::Dollars => Cow::Owned(math::to_dollars(&text)),
+                Math::Keep => text,
+            };
+            vars.set("text", &text);
+            vars.set("model", turn.model.as_deref().unwrap_or_default());
+            vars.set("message_id", turn.id.as_deref().unwrap_or_default());
+            vars.set("time", &turn.created.and_then(timestamp).unwrap_or_default());
+            vars.set("tool", turn.tool.as_ref().map(|t| t.name.as_str()).unwrap_or_default());
+            out.push_str(&vars.fill(if turn.tool.is_some() { &self.tool } else { &self.turn }));
+
+            for attachment in &turn.attachments {
+                vars.set("attachment", &markdown(attachment, lang));
+                vars.set("attachment_name", &attachment.name);
+                vars.set("attachment_path", attachment.path.as_deref().unwrap_or_default());
+                out.push_str(&vars.fill(&self.attachment));
+            }
+            for name in ["attachment", "attachment_name", "attachment_path"] {
+                vars.set(name, "");
+            }
+
+            if !turn.sources.is_empty() {
+                let list: Vec<String> = (1..).zip(&turn.sources).map(|(k, s)| format!("{}. {}", k, page(s))).collect();
+                vars.set("sources", &list.join("\n"));
+                out.push_str(&vars.fill(&self.sources));
+                vars.set("sources", "");
+                if state.citations == Citations::Footnotes {
+                    for (k, source) in (1..).zip(&turn.sources) {
+                        out.push_str(&format!("[^{}]: {}\n", notes + k, page(source)));
+                    }
+                    out.push('\n');
+                    state.notes += turn.sources.len();
+                }
+            }
+            if let Some(fork) = &turn.fork {
+                for variant in &fork.alternatives {
+                    out.push_str(&format!(
+                        "<details>\n<summary>{}</summary>\n\n",
+                        lang.trf("out.variant", &[&variant.number, &fork.total])
+                    ));
+                    self.push_turns(out, &variant.turns, first + i, state);
+                    out.push_str("</details>\n\n");
+                }
+            }
+        }
+    }
+}
+
+fn set_conversation(vars: &mut Vars, conv: &Conversation, lang: Lang) {
+    vars.set("title", &conv.title);
+    vars.set("source_url", &conv.source);
+    vars.set("share_id", conv.share_id.as_deref().unwrap_or_default());
+    vars.set("create_time", &conv.created.and_then(timestamp).unwrap_or_default());
+    vars.set("update_time", &conv.updated.and_then(timestamp).unwrap_or_default());
+    vars.set("fetched_at", &conv.fetched_at.format(&Rfc3339).unwrap_or_default());
+    vars.set("models", &conv.models().join(", "));
+    vars.set("message_count", &conv.turns.len().to_string());
+    vars.set("relay", conv.relay.as_deref().unwrap_or_default());
+    vars.set("label_source", lang.tr("out.source"));
+    vars.set("label_sources", lang.tr("out.sources"));
+}
+
+struct State {
+    lang: Lang,
+    citations: Citations,
+    math: Math,
+    vars: Vars,
+    // footnotes written so far
+    notes: usize,
+}
+
+// "[Title](https://...)"
+fn page(source: &WebSource) -> String {
+    format!("[{}]({})", link_text(&source.title), link(&source.url))
+}
+
+fn markdown(attachment: &Attachment, lang: Lang) -> String {
+    match &attachment.path {
+        Some(path) if attachment.image => format!("![{}]({})", link_text(&attachment.name), link(path)),
+        Some(path) => format!("[{}]({})", link_text(&attachment.name), link(path)),
+        None => format!("*{}*", attachment.placeholder(lang)),
+    }
+}
+
+// Brackets would end the text of a link early.
+fn link_text(text: &str) -> String {
+    text.replace('[', "\\[").replace(']', "\\]")
+}
+
+#[derive(Default)]
+struct Vars(Vec<(&'static str, String)>);
+
+impl Vars {
+    fn set(&mut self, name: &'static str, value: &str) {
+        match self.0.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, v)) => *v = value.to_string(),
+            None => self.0.push((name, value.to_string())),
+        }
+    }
+
+    fn fill(&self, template: &str) -> String {
+        let mut out = String::new();
+        let mut rest = template;
+        while let Some(start) = rest.find("{{") {
+            let Some(len) = rest[start..].find("}}") else { break };
+            let name = rest[start + 2..start + len].trim();
+            out.push_str(&rest[..start]);
+            out.push_str(self.0.iter().find(|(n, _)| *n == name).map(|(_, v)| v.as_str()).unwrap_or_default());
+            rest = &rest[start + len + 2..];
+        }
+        out.push_str(rest);
+        out
+    }
+}
+
+// (section, text) in file order; text before the first section comes under "".
+fn split(text: &str) -> Vec<(&str, String)> {
+    let mut sections = vec![("", String::new())];
+    for line in text.split_inclusive('\n') {
+        let name = line.trim_end().strip_prefix('[').and_then(|l| l.strip_suffix(']'));
+        match name {
+            Some(name) if SECTIONS.contains(&name) => sections.push((name, String::new())),
+            _ => sections.last_mut().expect("starts with a section").1.push_str(line),
+        }
+    }
+    sections
+}
+
+fn variables(text: &str) -> impl Iterator<Item = &str> {
+    text.split("{{").skip(1).filter_map(|part| part.split_once("}}")).map(|(name, _)| name.trim())
+}
+
+// `user = Me` lines; blank lines and `#` comments are skipped.
+fn roles(text: &str) -> Result<Vec<(Role, String)>> {
+    let mut roles = Vec::new();
+    for line in text.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+        let Some((id, label)) = line.split_once('=') else {
+            anyhow::bail!("expected `role = label` in [roles], got '{}'", line);
+        };
+        let Some(role) = Role::ALL.into_iter().find(|r| r.id() == id.trim()) else {
+            anyhow::bail!("unknown role '{}' in [roles] (expected user, assistant, tool or system)", id.trim());
+        };
+        roles.push((role, label.trim().to_string()));
+    }
+    Ok(roles)
+}
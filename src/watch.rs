@@ -0,0 +1,72 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_MINUTES: u64 = 15;
+// a file that keeps failing is checked at most every 16 intervals
+const MAX_DOUBLINGS: u32 = 4;
+
+// When each watched file is checked next. Files are checked every `interval`; every
+// failure in a row doubles the wait, so a relay that is down is not asked over and over.
+pub struct Schedule {
+    interval: Duration,
+    next: HashMap<PathBuf, Poll>,
+    running: HashSet<PathBuf>,
+}
+
+struct Poll {
+    at: Instant,
+    failures: u32,
+}
+
+impl Schedule {
+    pub fn new(minutes: u64) -> Schedule {
+        Schedule { interval: interval(minutes), next: HashMap::new(), running: HashSet::new() }
+    }
+
+    pub fn set_interval(&mut self, minutes: u64) {
+        self.interval = interval(minutes);
+    }
+
+    // Files not checked yet are due right away.
+    pub fn due(&self, path: &Path) -> bool {
+        !self.running(path) && self.next.get(path).is_none_or(|p| p.at <= Instant::now())
+    }
+
+    // Being checked or updated, by the watch or by hand; two updates of one file would
+    // both append the same turns.
+    pub fn running(&self, path: &Path) -> bool {
+        self.running.contains(path)
+    }
+
+    pub fn start(&mut self, path: &Path) {
+        self.running.insert(path.to_path_buf());
+    }
+
+    // An update by hand ended; when the file is checked next doesn't change.
+    pub fn stop(&mut self, path: &Path) {
+        self.running.remove(path);
+    }
+
+    // Records how a check went; returns the wait until the next one.
+    pub fn done(&mut self, path: &Path, ok: bool) -> Duration {
+        self.running.remove(path);
+        let failures = match self.next.get(path) {
+            Some(poll) if !ok => poll.failures + 1,
+            None if !ok => 1,
+            _ => 0,
+        };
+        let wait = self.interval * 2u32.pow(failures.min(MAX_DOUBLINGS));
+        self.next.insert(path.to_path_buf(), Poll { at: Instant::now() + wait, failures });
+        wait
+    }
+
+    // The earliest time a file that was checked before is due again.
+    pub fn next_due(&self) -> Option<Instant> {
+        self.next.values().map(|p| p.at).min()
+    }
+}
+
+fn interval(minutes: u64) -> Duration {
+    Duration::from_secs(60 * minutes.max(1))
+}